@@ -0,0 +1,212 @@
+// Tasks kept in other tools: todo.txt files, Markdown checklists and
+// Taskwarrior exports. Done tasks are left out, and the estimate of a
+// task is read from an "est:30m" token, a "(45m)" suffix or, for
+// Taskwarrior, an "estimate" attribute.
+
+use crate::config::parse_duration;
+use crate::ics;
+use chrono::Duration;
+use serde_json::Value;
+
+/// A task read from a backlog.
+#[derive(Debug)]
+pub struct Item {
+    /// An identifier that is stable across exports, if the format has
+    /// one.
+    pub uid: Option<String>,
+    pub description: String,
+    pub estimate: Option<Duration>,
+}
+
+/// What was read from a backlog: the pending tasks, the number of done
+/// tasks left out, and a description of what could not be read.
+#[derive(Debug, Default)]
+pub struct Backlog {
+    pub items: Vec<Item>,
+    pub done: usize,
+    pub problems: Vec<String>,
+}
+
+/// Reads a todo.txt file: one task per line, done tasks starting with
+/// "x ".
+pub fn read_todotxt(content: &str) -> Backlog {
+    let mut backlog = Backlog::default();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with("x ") {
+            backlog.done += 1;
+            continue;
+        }
+
+        // The priority and the creation date are not part of the
+        // description.
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        if words[0].len() == 3 && words[0].starts_with('(') && words[0].ends_with(')') {
+            words.remove(0);
+        }
+        if !words.is_empty() && is_date(words[0]) {
+            words.remove(0);
+        }
+
+        match item(&words.join(" ")) {
+            Ok(item) => backlog.items.push(item),
+            Err(problem) => backlog
+                .problems
+                .push(format!("line {}: {}", number + 1, problem)),
+        }
+    }
+    backlog
+}
+
+/// Reads the checklists of a Markdown file, like "- [ ] Write report".
+/// Other lines are ignored.
+pub fn read_markdown(content: &str) -> Backlog {
+    let mut backlog = Backlog::default();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim_start();
+        let rest = match line
+            .strip_prefix("- ")
+            .or_else(|| line.strip_prefix("* "))
+            .or_else(|| line.strip_prefix("+ "))
+        {
+            Some(rest) if rest.starts_with('[') => rest,
+            _ => continue,
+        };
+
+        // Links, like "- [akiv](https://...)", are not checklist items.
+        let (mark, description) = match (rest.get(1..2), rest.get(2..3), rest.get(3..4)) {
+            (Some(mark), Some("]"), Some(" ")) => (mark, &rest[3..]),
+            _ => continue,
+        };
+        match mark {
+            " " => match item(description) {
+                Ok(item) => backlog.items.push(item),
+                Err(problem) => backlog
+                    .problems
+                    .push(format!("line {}: {}", number + 1, problem)),
+            },
+            "x" | "X" => backlog.done += 1,
+            _ => backlog
+                .problems
+                .push(format!("line {}: unknown mark [{}].", number + 1, mark)),
+        }
+    }
+    backlog
+}
+
+/// Reads the output of "task export": a JSON array of tasks, or one
+/// task per line.
+pub fn read_taskwarrior(content: &str) -> Backlog {
+    let mut backlog = Backlog::default();
+    let tasks: Vec<(String, Value)> = match serde_json::from_str::<Vec<Value>>(content) {
+        Ok(tasks) => tasks
+            .into_iter()
+            .enumerate()
+            .map(|(index, task)| (format!("task {}", index + 1), task))
+            .collect(),
+        Err(_) => {
+            let mut tasks = Vec::new();
+            for (number, line) in content.lines().enumerate() {
+                let line = line.trim().trim_end_matches(',');
+                if line.is_empty() || line == "[" || line == "]" {
+                    continue;
+                }
+                match serde_json::from_str(line) {
+                    Ok(task) => tasks.push((format!("line {}", number + 1), task)),
+                    Err(error) => backlog
+                        .problems
+                        .push(format!("line {}: {}.", number + 1, error)),
+                }
+            }
+            tasks
+        }
+    };
+
+    for (place, task) in tasks {
+        match taskwarrior_item(&task) {
+            Ok(Some(item)) => backlog.items.push(item),
+            Ok(None) => backlog.done += 1,
+            Err(problem) => backlog.problems.push(format!("{}: {}", place, problem)),
+        }
+    }
+    backlog
+}
+
+/// Reads a Taskwarrior task, if it is not done.
+fn taskwarrior_item(task: &Value) -> Result<Option<Item>, String> {
+    match task.get("status").and_then(Value::as_str) {
+        Some("completed") | Some("deleted") => return Ok(None),
+        _ => {}
+    }
+    let description = match task.get("description").and_then(Value::as_str) {
+        Some(description) => description,
+        None => return Err("no description.".to_string()),
+    };
+    let mut item = item(description)?;
+    item.uid = task
+        .get("uuid")
+        .and_then(Value::as_str)
+        .map(|uuid| format!("{}@taskwarrior", uuid));
+
+    // An "estimate" user defined attribute, either a duration or a
+    // number of minutes.
+    match task.get("estimate") {
+        Some(Value::String(estimate)) => {
+            item.estimate = Some(
+                ics::parse_duration(estimate)
+                    .or_else(|| parse_duration(estimate).ok())
+                    .ok_or_else(|| format!("invalid estimate {}.", estimate))?,
+            )
+        }
+        Some(Value::Number(minutes)) => match minutes.as_f64() {
+            Some(minutes) if minutes >= 0.0 => {
+                item.estimate = Some(Duration::seconds((minutes * 60.0) as i64))
+            }
+            _ => return Err(format!("invalid estimate {}.", minutes)),
+        },
+        Some(estimate) => return Err(format!("invalid estimate {}.", estimate)),
+        None => {}
+    }
+    Ok(Some(item))
+}
+
+/// Splits the estimate from the description of a task.
+fn item(text: &str) -> Result<Item, String> {
+    let mut estimate = None;
+    let mut words = Vec::new();
+    for word in text.split_whitespace() {
+        match word.strip_prefix("est:") {
+            Some(value) => match parse_duration(value) {
+                Ok(duration) => estimate = Some(duration),
+                Err(_) => return Err(format!("invalid estimate {}.", value)),
+            },
+            None => words.push(word),
+        }
+    }
+    let mut description = words.join(" ");
+
+    if estimate.is_none() && description.ends_with(')') {
+        if let Some(open) = description.rfind('(') {
+            if let Ok(duration) = parse_duration(&description[open + 1..description.len() - 1]) {
+                estimate = Some(duration);
+                description = description[..open].trim_end().to_string();
+            }
+        }
+    }
+
+    if description.is_empty() {
+        return Err("no description.".to_string());
+    }
+    Ok(Item {
+        uid: None,
+        description,
+        estimate,
+    })
+}
+
+fn is_date(word: &str) -> bool {
+    chrono::NaiveDate::parse_from_str(word, "%Y-%m-%d").is_ok()
+}
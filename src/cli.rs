@@ -1,7 +1,10 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use structopt::StructOpt;
-use humantime::parse_duration;
-use chrono::Duration;
+use akiv::config::{parse_day, parse_duration, parse_time};
+use akiv::storage::Backend;
+use akiv::timesheet::{Grouping, Rounding};
+use chrono::{Duration, NaiveDate, NaiveTime};
 
 #[derive(Debug, StructOpt)]
 pub enum Command {
@@ -16,27 +19,318 @@ pub enum Command {
         description: String,
 
         /// The task's estimated duration.
-        #[structopt(parse(try_from_str=parse_chrono_duration))]
-        estimated_time: Duration
+        #[structopt(parse(try_from_str=parse_duration), required_unless = "auto")]
+        estimated_time: Option<Duration>,
 
+        /// Learn the estimated duration from similar finished tasks.
+        #[structopt(long, conflicts_with = "estimated-time")]
+        auto: bool,
+
+        /// Make the task an appointment starting at this time (HH:MM).
+        #[structopt(long, parse(try_from_str=parse_time))]
+        fixed: Option<NaiveTime>,
+    },
+    /// Suggest an estimate for a task, from similar finished tasks.
+    Suggest {
+        /// The task's description.
+        #[structopt()]
+        description: String,
     },
     /// Remove a task.
     Rm {
         #[structopt()]
         position: u32,
     },
+    /// Move a task to another position.
+    Mv {
+        #[structopt()]
+        from: u32,
+
+        #[structopt()]
+        to: u32,
+    },
     /// List all tasks in the journal file.
-    List,
+    List {
+        /// Show the forecast end times instead of the expected one.
+        #[structopt(long)]
+        forecast: bool,
+
+        /// List the tasks of every context.
+        #[structopt(long)]
+        all_contexts: bool,
+    },
+    /// Forecast when the remaining tasks will end, from past estimate errors.
+    Forecast,
+    /// Show how the remaining work fits in the day.
+    Plan {
+        /// Plan to stop working at this time (HH:MM) today.
+        #[structopt(long, parse(try_from_str=parse_time))]
+        until: Option<NaiveTime>,
+    },
     /// List all pauses in the journal file.
     Pauses,
+    /// Work on the active task in pomodoros, recording the breaks as pauses.
+    Pomodoro {
+        /// The length of the work intervals.
+        #[structopt(long, default_value = "25m", parse(try_from_str=parse_duration))]
+        work: Duration,
+
+        /// The length of the breaks between work intervals.
+        #[structopt(long, default_value = "5m", parse(try_from_str=parse_duration))]
+        short: Duration,
+
+        /// The length of the break after every fourth work interval.
+        #[structopt(long, default_value = "15m", parse(try_from_str=parse_duration))]
+        long: Duration,
+    },
     /// Mark current task as done, and advance to next task.
     Next,
+    /// Mark a task as done, even if it is not the active one.
+    Done {
+        #[structopt()]
+        position: u32,
+
+        /// Time spent on the task, if it was never started. Zero by default.
+        #[structopt(parse(try_from_str=parse_duration))]
+        duration: Option<Duration>,
+    },
+    /// Reopen a done task, making it the active task again.
+    Reopen {
+        #[structopt()]
+        position: u32,
+    },
+    /// Show the details of a task: its times, note and git repository.
+    Show {
+        #[structopt()]
+        position: u32,
+    },
+    /// Add a line to the note of a task.
+    Note {
+        #[structopt()]
+        position: u32,
+
+        #[structopt()]
+        text: String,
+    },
+    /// Export the tasks of a day.
+    Export(ExportCommand),
+    /// Import tasks for the day, from a calendar or a backlog.
+    Import {
+        /// The file to import, or its format followed by the file, like
+        /// "akiv import ics today.ics".
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+
+        #[structopt(parse(from_os_str), hidden = true)]
+        format_file: Option<PathBuf>,
+
+        /// The format of the file. Guessed from its extension by default.
+        #[structopt(long, possible_values = &["ics", "todotxt", "markdown", "taskwarrior"])]
+        format: Option<ImportFormat>,
+    },
+    /// Copy a journal to a new file, possibly with another backend.
+    /// Everything is copied, but task and break ids are numbered again.
+    Convert {
+        #[structopt(parse(from_os_str))]
+        from: PathBuf,
+
+        #[structopt(parse(from_os_str))]
+        to: PathBuf,
+
+        /// The backend of the new journal. Guessed from its path by
+        /// default.
+        #[structopt(long, possible_values = &["sqlite", "text"])]
+        to_backend: Option<Backend>,
+    },
+    /// Serve the journal as a JSON API over HTTP.
+    Serve {
+        /// The address to listen on.
+        #[structopt(long, default_value = "127.0.0.1:8080")]
+        listen: String,
+    },
+    /// Add the tasks and work of another journal, from another machine.
+    Merge {
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Summarize the last working day and today's plan, for a standup.
+    Standup {
+        /// Report the tasks finished since this day (YYYY-MM-DD) instead
+        /// of the last working day.
+        #[structopt(long, parse(try_from_str=parse_day))]
+        since: Option<NaiveDate>,
+
+        /// Print as Markdown.
+        #[structopt(long)]
+        markdown: bool,
+    },
+    /// Export the time worked on finished tasks as CSV.
+    Timesheet {
+        /// The first day (YYYY-MM-DD). The last day by default.
+        #[structopt(long, parse(try_from_str=parse_day))]
+        from: Option<NaiveDate>,
+
+        /// The last day (YYYY-MM-DD). Today by default.
+        #[structopt(long, parse(try_from_str=parse_day))]
+        to: Option<NaiveDate>,
+
+        /// One entry per task, day, or day and tag.
+        #[structopt(long, default_value = "task", possible_values = &["task", "day", "tag"])]
+        group: Grouping,
+
+        /// Round durations to the nearest increment, or up.
+        #[structopt(long, possible_values = &["nearest", "up"])]
+        round: Option<Rounding>,
+
+        /// The rounding increment, in minutes.
+        #[structopt(long)]
+        increment: Option<u32>,
+
+        /// Write to this file instead of the standard output.
+        #[structopt(parse(from_os_str), short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Manage the breaks planned for the day.
+    Break(BreakCommand),
+    /// Switch between separate lists of tasks, like work and personal.
+    Context(ContextCommand),
     /// Start working
     Start,
     /// Stop working
     Stop,
 }
 
+impl Command {
+    /// Whether the command changes the journal. The ones running for
+    /// long, like 'serve' and 'pomodoro', change journals of their own.
+    pub fn changes_journal(&self) -> bool {
+        match self {
+            Command::Add { .. }
+            | Command::Rm { .. }
+            | Command::Mv { .. }
+            | Command::Next
+            | Command::Done { .. }
+            | Command::Reopen { .. }
+            | Command::Note { .. }
+            | Command::Import { .. }
+            | Command::Merge { .. }
+            | Command::Break(BreakCommand::Add { .. })
+            | Command::Break(BreakCommand::Rm { .. })
+            | Command::Context(ContextCommand::Use { .. })
+            | Command::Start
+            | Command::Stop => true,
+            Command::Plan { until } => until.is_some(),
+            Command::Suggest { .. }
+            | Command::List { .. }
+            | Command::Forecast
+            | Command::Pauses
+            | Command::Pomodoro { .. }
+            | Command::Show { .. }
+            | Command::Export(_)
+            | Command::Convert { .. }
+            | Command::Serve { .. }
+            | Command::Standup { .. }
+            | Command::Timesheet { .. }
+            | Command::Break(BreakCommand::List)
+            | Command::Context(ContextCommand::List) => false,
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub enum ExportCommand {
+    /// Export as an iCalendar file.
+    Ics {
+        /// The day to export (YYYY-MM-DD). Today by default.
+        #[structopt(long, parse(try_from_str=parse_day))]
+        day: Option<NaiveDate>,
+
+        /// Write to this file instead of the standard output.
+        #[structopt(parse(from_os_str), short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Export as a Markdown checklist, to paste in a daily note.
+    Markdown {
+        /// The day to export (YYYY-MM-DD). Today by default.
+        #[structopt(long, parse(try_from_str=parse_day))]
+        day: Option<NaiveDate>,
+
+        /// Write to this file instead of the standard output.
+        #[structopt(parse(from_os_str), short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ImportFormat {
+    /// The day's events of an iCalendar file, as appointments.
+    Ics,
+    TodoTxt,
+    /// The checklists of a Markdown file.
+    Markdown,
+    /// The output of "task export".
+    Taskwarrior,
+}
+
+impl ImportFormat {
+    /// Guesses the format of a file from its extension.
+    pub fn from_extension(file: &Path) -> Option<ImportFormat> {
+        match file.extension()?.to_str()?.to_lowercase().as_str() {
+            "ics" => Some(ImportFormat::Ics),
+            "txt" => Some(ImportFormat::TodoTxt),
+            "md" | "markdown" => Some(ImportFormat::Markdown),
+            "json" => Some(ImportFormat::Taskwarrior),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for ImportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<ImportFormat> {
+        match s {
+            "ics" => Ok(ImportFormat::Ics),
+            "todotxt" => Ok(ImportFormat::TodoTxt),
+            "markdown" => Ok(ImportFormat::Markdown),
+            "taskwarrior" => Ok(ImportFormat::Taskwarrior),
+            _ => anyhow::bail!("Unknown format {}.", s),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub enum BreakCommand {
+    /// Plan a break for the day.
+    Add {
+        /// The break's start time (HH:MM).
+        #[structopt(parse(try_from_str=parse_time))]
+        start: NaiveTime,
+
+        /// The break's duration.
+        #[structopt(parse(try_from_str=parse_duration))]
+        duration: Duration,
+    },
+    /// Remove a planned break.
+    Rm {
+        #[structopt()]
+        id: u32,
+    },
+    /// List the breaks planned for the day.
+    List,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum ContextCommand {
+    /// Use a context until another one is used.
+    Use {
+        #[structopt()]
+        name: String,
+    },
+    /// List the contexts.
+    List,
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt(
     name = "Akiv",
@@ -49,9 +343,17 @@ pub struct CommandLineArgs {
     /// Use a different journal file.
     #[structopt(parse(from_os_str), short, long)]
     pub journal_file: Option<PathBuf>,
-}
 
-fn parse_chrono_duration(s: &str) -> anyhow::Result<Duration> {
-    let duration = parse_duration(s)?;
-    Ok(Duration::from_std(duration)?)
+    /// Keep the journal in SQLite, in memory or in a text file (guessed
+    /// from the journal file by default).
+    #[structopt(long, possible_values = &["sqlite", "memory", "text"])]
+    pub backend: Option<Backend>,
+
+    /// Use a context other than the current one, for this command.
+    #[structopt(long)]
+    pub context: Option<String>,
+
+    /// Use a different config file.
+    #[structopt(parse(from_os_str), short, long)]
+    pub config_file: Option<PathBuf>,
 }
@@ -0,0 +1,144 @@
+// Where the current time comes from, and which timezone it is read in.
+// The journal takes every timestamp and the current day from a 'Clock',
+// so that scenarios can be replayed at a given time, by tests or scripts
+// setting AKIV_NOW.
+//
+// Timestamps are instants, stored in UTC. The 'Timezone' only decides
+// which day an instant belongs to, when the times of the day, like the
+// end of work or appointments, happen, and how times are shown.
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{
+    DateTime, Duration, FixedOffset, Local, LocalResult, NaiveDate, NaiveDateTime, NaiveTime,
+    Offset, TimeZone, Timelike,
+};
+use chrono_tz::Tz;
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// The environment variable stopping the clock at a given time, like
+/// "2021-05-03T09:00:00+02:00".
+pub const NOW_VARIABLE: &str = "AKIV_NOW";
+
+pub trait Clock {
+    /// The current time, with seconds precision.
+    fn now(&self) -> DateTime<Local>;
+
+    /// Waits for the given time to pass. Clocks replaying scenarios can
+    /// move forward instead.
+    fn sleep(&self, duration: Duration) {
+        if let Ok(duration) = duration.to_std() {
+            std::thread::sleep(duration);
+        }
+    }
+}
+
+/// The time of the system.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        let now = Local::now();
+        now.with_nanosecond(0).unwrap_or(now)
+    }
+}
+
+/// A clock stopped at a given time.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock(pub DateTime<Local>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Local> {
+        self.0
+    }
+}
+
+/// The clock stopped at AKIV_NOW if it is set, and the system one
+/// otherwise.
+pub fn from_env() -> Result<Rc<dyn Clock>> {
+    match std::env::var(NOW_VARIABLE) {
+        Ok(now) => {
+            let time = DateTime::parse_from_rfc3339(&now).with_context(|| {
+                format!(
+                    "Invalid {} '{}', expected a time like 2021-05-03T09:00:00+02:00.",
+                    NOW_VARIABLE, now
+                )
+            })?;
+            Ok(Rc::new(FixedClock(time.with_timezone(&Local))))
+        }
+        Err(_) => Ok(Rc::new(SystemClock)),
+    }
+}
+
+/// The timezone days are counted in: the one of the system, which
+/// follows the user when travelling, or a named one, like
+/// "Europe/Paris".
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Timezone {
+    #[default]
+    System,
+    Named(Tz),
+}
+
+impl FromStr for Timezone {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Timezone> {
+        match s {
+            "system" => Ok(Timezone::System),
+            _ => s
+                .parse()
+                .map(Timezone::Named)
+                .map_err(|_| anyhow!("Unknown timezone '{}'", s)),
+        }
+    }
+}
+
+impl Timezone {
+    /// The day an instant belongs to.
+    pub fn day(self, instant: DateTime<Local>) -> NaiveDate {
+        self.localize(instant).date().naive_local()
+    }
+
+    /// The instant a day reaches the given time. When the clocks go
+    /// back, the first of the two is taken, and when they go forward,
+    /// a time in the gap is read with the offset before the change, so
+    /// that 02:30 is 03:30 when the clocks skip from 02:00 to 03:00.
+    pub fn at(self, day: NaiveDate, time: NaiveTime) -> Option<DateTime<Local>> {
+        let local = day.and_time(time);
+        match self {
+            Timezone::System => at_local(&Local, local),
+            Timezone::Named(tz) => at_local(&tz, local).map(|time| time.with_timezone(&Local)),
+        }
+    }
+
+    /// An instant with the offset of the timezone, for display.
+    pub fn localize(self, instant: DateTime<Local>) -> DateTime<FixedOffset> {
+        match self {
+            Timezone::System => {
+                let time = instant.with_timezone(&Local);
+                time.with_timezone(&time.offset().fix())
+            }
+            Timezone::Named(tz) => {
+                let time = instant.with_timezone(&tz);
+                time.with_timezone(&time.offset().fix())
+            }
+        }
+    }
+}
+
+fn at_local<T: TimeZone>(tz: &T, local: NaiveDateTime) -> Option<DateTime<T>> {
+    match tz.from_local_datetime(&local) {
+        LocalResult::None => {
+            // No change of offset lasts more than a few hours.
+            let before = tz
+                .offset_from_local_datetime(&(local - Duration::hours(3)))
+                .earliest()?
+                .fix();
+            let utc = local - Duration::seconds(before.local_minus_utc().into());
+            Some(tz.from_utc_datetime(&utc))
+        }
+        result => result.earliest(),
+    }
+}
@@ -0,0 +1,215 @@
+// User settings, read from a TOML file. Every setting is optional, and
+// a missing file is the same as an empty one.
+
+use crate::clock::Timezone;
+use crate::timesheet::Rounding;
+use anyhow::{Context, Result};
+use chrono::{Duration, NaiveDate, NaiveTime};
+use serde::{Deserialize, Deserializer};
+use std::path::Path;
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    /// The time at which the work day is expected to end, unless
+    /// another one is planned for the day.
+    #[serde(deserialize_with = "deserialize_optional_time")]
+    pub end_of_work: Option<NaiveTime>,
+
+    /// The timezone days are counted in, like "Europe/Paris". By
+    /// default, the one of the system.
+    #[serde(deserialize_with = "deserialize_timezone")]
+    pub timezone: Timezone,
+
+    /// Breaks taken every day.
+    #[serde(rename = "break")]
+    pub breaks: Vec<DailyBreak>,
+
+    /// How the durations of the timesheets are rounded.
+    pub timesheet: TimesheetConfig,
+
+    /// Record the git repository of the working directory on the
+    /// tasks started, and the commits made until they are finished.
+    pub git_context: bool,
+
+    /// Commands run when the work state or the tasks change.
+    pub hooks: HooksConfig,
+
+    /// URLs notified when the work state or the active task change.
+    pub webhooks: WebhooksConfig,
+
+    /// How the previous days left running are closed.
+    pub unclosed_days: UnclosedDaysConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct TimesheetConfig {
+    pub rounding: Rounding,
+    /// In minutes.
+    pub increment: u32,
+}
+
+impl Default for TimesheetConfig {
+    fn default() -> TimesheetConfig {
+        TimesheetConfig {
+            rounding: Rounding::Nearest,
+            increment: 15,
+        }
+    }
+}
+
+/// Shell commands run after each command changing the tasks or the
+/// work state, if set.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HooksConfig {
+    pub start: Option<String>,
+    pub stop: Option<String>,
+    pub next: Option<String>,
+    pub done: Option<String>,
+    pub reopen: Option<String>,
+    pub add: Option<String>,
+    pub remove: Option<String>,
+    /// Hooks running longer are killed.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub timeout: Duration,
+}
+
+impl Default for HooksConfig {
+    fn default() -> HooksConfig {
+        HooksConfig {
+            start: None,
+            stop: None,
+            next: None,
+            done: None,
+            reopen: None,
+            add: None,
+            remove: None,
+            timeout: Duration::seconds(10),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WebhooksConfig {
+    pub urls: Vec<String>,
+    /// How long to wait for each request.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub timeout: Duration,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> WebhooksConfig {
+        WebhooksConfig {
+            urls: Vec::new(),
+            timeout: Duration::seconds(5),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct UnclosedDaysConfig {
+    pub close_at: CloseAt,
+    pub active_task: CloseTask,
+}
+
+/// When a day left running is closed.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CloseAt {
+    /// Ask for a time, and what to do with the active task.
+    #[default]
+    Ask,
+    /// At the last time something was recorded that day, if work was
+    /// stopped. Days left working are closed at their end of work, or
+    /// asked about.
+    LastActivity,
+    /// At the end of work of the day, unless the last activity came
+    /// later. Without one, like 'LastActivity'.
+    EndOfWork,
+}
+
+/// What becomes of the task left active on a day that is closed.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CloseTask {
+    /// The task is done, when the day is closed.
+    Finish,
+    /// The task is finished when the day is closed, and added again to
+    /// the current day for the time left.
+    #[default]
+    CarryOver,
+}
+
+/// A break taken every day, in addition to the ones planned for the
+/// day.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DailyBreak {
+    #[serde(deserialize_with = "deserialize_time")]
+    pub start: NaiveTime,
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub duration: Duration,
+}
+
+/// Read the settings from the given file, if it exists.
+pub fn load(path: Option<&Path>) -> Result<Config> {
+    match path {
+        Some(path) if path.exists() => {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read config file {}.", path.display()))?;
+            toml::from_str(&content)
+                .with_context(|| format!("Failed to parse config file {}.", path.display()))
+        }
+        _ => Ok(Config::default()),
+    }
+}
+
+/// Parse a time of the day, as HH:MM or HH:MM:SS.
+pub fn parse_time(s: &str) -> Result<NaiveTime> {
+    Ok(NaiveTime::parse_from_str(s, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(s, "%H:%M:%S"))?)
+}
+
+/// Parse a day, as YYYY-MM-DD.
+pub fn parse_day(s: &str) -> Result<NaiveDate> {
+    Ok(NaiveDate::parse_from_str(s, "%Y-%m-%d")?)
+}
+
+/// Parse a duration, like "45m" or "1h 30m".
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    Ok(Duration::from_std(humantime::parse_duration(s)?)?)
+}
+
+fn deserialize_time<'de, D>(deserializer: D) -> Result<NaiveTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    parse_time(&s).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_optional_time<'de, D>(deserializer: D) -> Result<Option<NaiveTime>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_time(deserializer).map(Some)
+}
+
+fn deserialize_duration<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    parse_duration(&s).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_timezone<'de, D>(deserializer: D) -> Result<Timezone, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(serde::de::Error::custom)
+}
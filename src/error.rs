@@ -0,0 +1,61 @@
+// The errors of the journal operations. Failures of the storage are
+// kept as they are, the other errors tell why an operation is not
+// allowed.
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Unexisting task.")]
+    NoSuchTask(u32),
+
+    #[error("Task {0} is already done.")]
+    AlreadyDone(u32),
+
+    #[error("Only done tasks can be reopened.")]
+    NotDone(u32),
+
+    #[error("You can only remove non started tasks.")]
+    AlreadyStarted(u32),
+
+    #[error("Only tasks not started yet can be moved.")]
+    MoveStarted(u32),
+
+    #[error("A duration can only be given for tasks that were never started.")]
+    DurationOfStartedTask(u32),
+
+    #[error("Task {0} is active. Use 'akiv done {0}' before reopening another task.")]
+    AnotherTaskActive(u32),
+
+    #[error("Unexisting break.")]
+    NoSuchBreak(u32),
+
+    #[error("You are already working!")]
+    AlreadyWorking,
+
+    #[error("Not running.")]
+    NotWorking,
+
+    #[error("There are no tasks to work on!")]
+    NoTasks,
+
+    #[error("Work is stopped. Use 'akiv start' before moving to next task.")]
+    WorkStopped,
+
+    #[error("No similar finished tasks to learn an estimate from.")]
+    NoSimilarTasks,
+
+    #[error("Not enough finished tasks to forecast: {found} found, at least {needed} needed.")]
+    NotEnoughHistory { found: usize, needed: usize },
+
+    #[error("A day can only be closed after its last activity.")]
+    CloseBeforeLastActivity,
+
+    #[error("Invalid context '{0}', context names are single words.")]
+    InvalidContext(String),
+
+    #[error(transparent)]
+    Storage(#[from] anyhow::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
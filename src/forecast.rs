@@ -0,0 +1,136 @@
+// Forecast of the end times of the unfinished tasks, based on how long
+// the finished tasks actually took compared to their estimates.
+//
+// Each simulation draws, for every unfinished task, the ratio of actual
+// to estimated duration of a random finished task, and projects the
+// plan with the resulting durations. Appointments are supposed to take
+// the time they were planned for.
+
+use crate::clock::Timezone;
+use crate::model::TaskExtra;
+use crate::model::{Task, TaskState};
+use crate::plan;
+use chrono::{DateTime, Duration, Local};
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+/// The percentiles of the end times reported by the forecast.
+pub const PERCENTILES: [usize; 3] = [50, 80, 95];
+
+/// The number of simulations run for a forecast.
+const SIMULATIONS: usize = 2000;
+
+/// The minimal number of finished tasks needed to forecast.
+pub const MIN_HISTORY: usize = 5;
+
+/// The end times of the unfinished tasks, and of the day, for each of
+/// the PERCENTILES.
+#[derive(Debug)]
+pub struct Forecast {
+    /// One entry per task, in the same order. Done tasks have none.
+    pub tasks: Vec<Option<[DateTime<Local>; 3]>>,
+    /// When the last unfinished task ends, if any.
+    pub day: Option<[DateTime<Local>; 3]>,
+}
+
+/// Returns the ratios of actual to estimated duration of the given
+/// finished tasks, with the time worked on each of them. Tasks without
+/// an estimate, and the ones marked done without any time worked, are
+/// left out.
+pub fn ratios(finished_tasks: &[(Task, Duration)]) -> Vec<f64> {
+    finished_tasks
+        .iter()
+        .filter(|(task, ellapsed)| {
+            task.estimated_duration > Duration::seconds(0) && *ellapsed > Duration::seconds(0)
+        })
+        .map(|(task, ellapsed)| {
+            ellapsed.num_seconds() as f64 / task.estimated_duration.num_seconds() as f64
+        })
+        .collect()
+}
+
+/// Forecasts the end times of the given tasks, starting from 'now'.
+/// 'ellapsed_times' are the times already worked on each task, and
+/// 'ratios' must not be empty.
+pub fn forecast<R: Rng>(
+    tasks: &[Task],
+    ellapsed_times: &[Duration],
+    breaks: &[(DateTime<Local>, DateTime<Local>)],
+    ratios: &[f64],
+    now: DateTime<Local>,
+    timezone: Timezone,
+    rng: &mut R,
+) -> Forecast {
+    let mut task_ends: Vec<Vec<DateTime<Local>>> = tasks.iter().map(|_| Vec::new()).collect();
+    let mut day_ends = Vec::new();
+
+    for _ in 0..SIMULATIONS {
+        let remaining_times: Vec<Duration> = tasks
+            .iter()
+            .zip(ellapsed_times.iter())
+            .map(|(task, ellapsed)| simulated_remaining_time(task, *ellapsed, ratios, rng))
+            .collect();
+
+        let projections = plan::project_with(tasks, &remaining_times, breaks, now, timezone);
+        let mut day_end = None;
+        for (ends, projection) in task_ends.iter_mut().zip(projections.iter()) {
+            if let Some(projection) = projection {
+                ends.push(projection.end);
+                day_end = std::cmp::max(day_end, Some(projection.end));
+            }
+        }
+        day_ends.extend(day_end);
+    }
+
+    Forecast {
+        tasks: task_ends.iter_mut().map(|ends| percentiles(ends)).collect(),
+        day: percentiles(&mut day_ends),
+    }
+}
+
+/// Draws the time left to finish a task. The active task is supposed to
+/// take longer than the time already worked on it, if the history
+/// allows it.
+fn simulated_remaining_time<R: Rng>(
+    task: &Task,
+    ellapsed: Duration,
+    ratios: &[f64],
+    rng: &mut R,
+) -> Duration {
+    if task.fixed_at.is_some() || task.is_done() {
+        return std::cmp::max(Duration::seconds(0), task.estimated_duration - ellapsed);
+    }
+
+    let estimated = task.estimated_duration.num_seconds() as f64;
+    let ratio = match task.state() {
+        TaskState::Active => {
+            let longer: Vec<f64> = ratios
+                .iter()
+                .copied()
+                .filter(|ratio| ratio * estimated > ellapsed.num_seconds() as f64)
+                .collect();
+            longer.choose(rng).copied()
+        }
+        _ => ratios.choose(rng).copied(),
+    };
+
+    match ratio {
+        Some(ratio) => std::cmp::max(
+            Duration::seconds(0),
+            Duration::seconds((ratio * estimated) as i64) - ellapsed,
+        ),
+        None => Duration::seconds(0),
+    }
+}
+
+fn percentiles(values: &mut [DateTime<Local>]) -> Option<[DateTime<Local>; 3]> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort();
+    let at = |percentile: usize| {
+        let rank = (percentile * values.len()).div_ceil(100);
+        values[std::cmp::max(rank, 1) - 1]
+    };
+    Some([at(PERCENTILES[0]), at(PERCENTILES[1]), at(PERCENTILES[2])])
+}
@@ -0,0 +1,46 @@
+// The git repository tasks are worked in, to tie the time spent on a
+// task to the changes made meanwhile. Outside of a repository, or
+// without git, there is simply no context to record.
+
+use crate::model::GitContext;
+use std::path::Path;
+use std::process::Command;
+
+/// The repository, branch and HEAD commit of the working tree 'dir'
+/// is in, if any.
+pub fn capture(dir: &Path) -> Option<GitContext> {
+    let repository = git(dir, &["rev-parse", "--show-toplevel"])?;
+    let head = git(dir, &["rev-parse", "HEAD"])?;
+    let branch = git(dir, &["symbolic-ref", "--short", "-q", "HEAD"]);
+    Some(GitContext {
+        repository,
+        branch,
+        head,
+        commits: Vec::new(),
+    })
+}
+
+/// The commits made in the repository since the context was captured,
+/// oldest first, like "1a2b3c4 Fix parsing".
+pub fn commits_since(git_context: &GitContext) -> Option<Vec<String>> {
+    let range = format!("{}..HEAD", git_context.head);
+    let log = git(
+        Path::new(&git_context.repository),
+        &["log", "--reverse", "--format=%h %s", &range],
+    )?;
+    Some(log.lines().map(str::to_string).collect())
+}
+
+/// The output of a git command, trimmed, if it succeeded.
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
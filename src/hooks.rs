@@ -0,0 +1,128 @@
+// Shell commands run after the commands changing the tasks or the work
+// state, to update other tools, like a chat status or a music player.
+// The hook of an event is run with 'sh -c', and is given the event, the
+// task and the work state both as environment variables:
+//
+//     AKIV_EVENT          start, stop, next, done, reopen, add or remove
+//     AKIV_CONTEXT        the context of the task
+//     AKIV_WORK_STATE     running or stopped, after the event
+//     AKIV_TASK_POSITION, AKIV_TASK_DESCRIPTION, AKIV_TASK_UUID
+//
+// and as JSON on its standard input, with the task finished by 'next' or
+// 'done'. For these two, the task is the active one after the event.
+// A hook that fails or runs longer than the timeout is reported, but
+// the command it follows still succeeds: the change is already saved.
+
+use crate::json::task_json;
+use crate::model::{Task, WorkState};
+use crate::Journal;
+use anyhow::{bail, Context, Result};
+use serde_json::json;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Event {
+    Start,
+    Stop,
+    Next,
+    Done,
+    Reopen,
+    Add,
+    Remove,
+}
+
+impl Event {
+    fn name(self) -> &'static str {
+        match self {
+            Event::Start => "start",
+            Event::Stop => "stop",
+            Event::Next => "next",
+            Event::Done => "done",
+            Event::Reopen => "reopen",
+            Event::Add => "add",
+            Event::Remove => "remove",
+        }
+    }
+}
+
+/// Runs the hook of an event, if one is set. 'task' is the task the
+/// event is about, and 'finished' the task finished by 'next'.
+pub fn run(journal: &Journal, event: Event, task: Option<&Task>, finished: Option<&Task>) {
+    let hooks = &journal.config().hooks;
+    let command = match event {
+        Event::Start => &hooks.start,
+        Event::Stop => &hooks.stop,
+        Event::Next => &hooks.next,
+        Event::Done => &hooks.done,
+        Event::Reopen => &hooks.reopen,
+        Event::Add => &hooks.add,
+        Event::Remove => &hooks.remove,
+    };
+    if let Some(command) = command {
+        if let Err(error) = run_command(journal, command, event, task, finished) {
+            eprintln!("Hook '{}' failed: {:#}", event.name(), error);
+        }
+    }
+}
+
+fn run_command(
+    journal: &Journal,
+    command: &str,
+    event: Event,
+    task: Option<&Task>,
+    finished: Option<&Task>,
+) -> Result<()> {
+    let work_state = match journal.work_state()? {
+        WorkState::Running => "running",
+        WorkState::Stopped => "stopped",
+    };
+    let input = json!({
+        "event": event.name(),
+        "context": journal.context(),
+        "work_state": work_state,
+        "task": task.map(task_json),
+        "finished": finished.map(task_json),
+    });
+
+    let mut hook = Command::new("sh");
+    hook.arg("-c")
+        .arg(command)
+        .env("AKIV_EVENT", event.name())
+        .env("AKIV_CONTEXT", journal.context())
+        .env("AKIV_WORK_STATE", work_state)
+        .stdin(Stdio::piped());
+    if let Some(task) = task {
+        hook.env("AKIV_TASK_POSITION", task.position.to_string())
+            .env("AKIV_TASK_DESCRIPTION", &task.description)
+            .env("AKIV_TASK_UUID", &task.uuid);
+    }
+    let mut child = hook.spawn().context("Failed to run hook.")?;
+
+    // Hooks are free to ignore their input: it is written from another
+    // thread, so that a hook not reading it cannot block past the
+    // timeout. The pipe is closed once written.
+    if let Some(mut stdin) = child.stdin.take() {
+        std::thread::spawn(move || {
+            let _ = writeln!(stdin, "{}", input);
+        });
+    }
+
+    let timeout = journal.config().hooks.timeout.to_std()?;
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            if !status.success() {
+                bail!("{}.", status);
+            }
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            child.kill()?;
+            child.wait()?;
+            bail!("Killed after {}.", humantime::format_duration(timeout));
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
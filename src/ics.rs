@@ -0,0 +1,569 @@
+// iCalendar (RFC 5545) support, limited to what akiv needs: writing a
+// calendar of events, and reading the events of a day from a calendar.
+
+use crate::clock::Timezone;
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc,
+    Weekday,
+};
+use chrono_tz::Tz;
+
+/// The product identifier written in the calendars.
+const PRODUCT_ID: &str = "-//gozque//akiv//EN";
+
+/// A calendar event.
+#[derive(Debug)]
+pub struct Event {
+    pub uid: String,
+    pub summary: String,
+    pub description: String,
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    /// Whether the event is only expected to happen.
+    pub tentative: bool,
+}
+
+/// Returns the calendar containing the given events.
+pub fn write_calendar(events: &[Event], stamp: DateTime<Local>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODUCT_ID),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}", escape(&event.uid)));
+        lines.push(format!("DTSTAMP:{}", format_utc(stamp)));
+        lines.push(format!("DTSTART:{}", format_utc(event.start)));
+        lines.push(format!("DTEND:{}", format_utc(event.end)));
+        lines.push(format!("SUMMARY:{}", escape(&event.summary)));
+        if !event.description.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape(&event.description)));
+        }
+        lines.push(format!(
+            "STATUS:{}",
+            if event.tentative {
+                "TENTATIVE"
+            } else {
+                "CONFIRMED"
+            }
+        ));
+        lines.push("END:VEVENT".to_string());
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut calendar = String::new();
+    for line in lines {
+        calendar.push_str(&fold(&line));
+        calendar.push_str("\r\n");
+    }
+    calendar
+}
+
+/// Formats a date time as a UTC date time value.
+fn format_utc<Tz: TimeZone>(date_time: DateTime<Tz>) -> String {
+    date_time
+        .with_timezone(&Utc)
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+/// Escapes a text value.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Folds a content line so that no line is longer than 75 octets, without
+/// splitting characters.
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut line_length = 0;
+    for c in line.chars() {
+        if line_length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            line_length = 1;
+        }
+        folded.push(c);
+        line_length += c.len_utf8();
+    }
+    folded
+}
+
+/// An event read from a calendar, taking place on a given day.
+#[derive(Debug)]
+pub struct CalendarEvent {
+    pub uid: String,
+    pub summary: String,
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+}
+
+/// A property of a calendar component, like
+/// "DTSTART;TZID=Europe/Paris:20210503T140000".
+#[derive(Debug)]
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// The time zone of a date time value.
+#[derive(Debug, Clone, Copy)]
+enum Zone {
+    Utc,
+    Named(Tz),
+    /// Floating times, and times in unknown time zones, are read in
+    /// the timezone of the journal.
+    Floating,
+}
+
+impl Zone {
+    fn to_local(self, date_time: NaiveDateTime, timezone: Timezone) -> Option<DateTime<Local>> {
+        match self {
+            Zone::Utc => Some(Utc.from_utc_datetime(&date_time).with_timezone(&Local)),
+            Zone::Named(tz) => tz
+                .from_local_datetime(&date_time)
+                .earliest()
+                .map(|date_time| date_time.with_timezone(&Local)),
+            Zone::Floating => timezone.at(date_time.date(), date_time.time()),
+        }
+    }
+
+    fn in_zone(self, date_time: DateTime<Local>, timezone: Timezone) -> NaiveDateTime {
+        match self {
+            Zone::Utc => date_time.naive_utc(),
+            Zone::Named(tz) => date_time.with_timezone(&tz).naive_local(),
+            Zone::Floating => timezone.localize(date_time).naive_local(),
+        }
+    }
+}
+
+/// A date time value, in its time zone.
+#[derive(Debug, Clone, Copy)]
+struct ZonedDateTime {
+    date_time: NaiveDateTime,
+    zone: Zone,
+}
+
+/// Returns the events of the calendar taking place on the given day of
+/// the timezone, and the reasons why some events could not be read.
+/// All-day and cancelled events are left out.
+pub fn read_events(
+    calendar: &str,
+    day: NaiveDate,
+    timezone: Timezone,
+) -> (Vec<CalendarEvent>, Vec<String>) {
+    let mut events = Vec::new();
+    let mut problems = Vec::new();
+
+    let components = read_components(calendar, "VEVENT");
+
+    // Modified occurrences of recurring events replace the regular ones.
+    let mut modified: Vec<(String, NaiveDate)> = Vec::new();
+    for properties in &components {
+        if let (Some(uid), Some(recurrence_id)) =
+            (find(properties, "UID"), find(properties, "RECURRENCE-ID"))
+        {
+            match parse_date_time(recurrence_id) {
+                Ok(Some(recurrence)) => {
+                    if let Some(recurrence) =
+                        recurrence.zone.to_local(recurrence.date_time, timezone)
+                    {
+                        modified.push((uid.value.clone(), timezone.day(recurrence)));
+                    }
+                }
+                Ok(None) => {}
+                Err(problem) => problems.push(problem),
+            }
+        }
+    }
+
+    for properties in &components {
+        match read_event(properties, day, timezone, &modified) {
+            Ok(Some(event)) => events.push(event),
+            Ok(None) => {}
+            Err(problem) => problems.push(problem),
+        }
+    }
+
+    events.sort_by_key(|event| event.start);
+    (events, problems)
+}
+
+/// Reads an event, if it takes place on the given day.
+fn read_event(
+    properties: &[Property],
+    day: NaiveDate,
+    timezone: Timezone,
+    modified: &[(String, NaiveDate)],
+) -> Result<Option<CalendarEvent>, String> {
+    let summary = find(properties, "SUMMARY")
+        .map(|property| unescape(&property.value))
+        .unwrap_or_default();
+
+    if matches!(find(properties, "STATUS"), Some(status) if status.value == "CANCELLED") {
+        return Ok(None);
+    }
+
+    let start = match find(properties, "DTSTART") {
+        Some(property) => match parse_date_time(property) {
+            Ok(Some(start)) => start,
+            // all-day event
+            Ok(None) => return Ok(None),
+            Err(problem) => return Err(format!("{}: {}", summary, problem)),
+        },
+        None => return Err(format!("{}: no start.", summary)),
+    };
+
+    let duration = match (find(properties, "DTEND"), find(properties, "DURATION")) {
+        (Some(end), _) => match parse_date_time(end) {
+            Ok(Some(end)) => match (
+                start.zone.to_local(start.date_time, timezone),
+                end.zone.to_local(end.date_time, timezone),
+            ) {
+                (Some(start), Some(end)) => end - start,
+                _ => return Err(format!("{}: invalid time.", summary)),
+            },
+            Ok(None) => return Ok(None),
+            Err(problem) => return Err(format!("{}: {}", summary, problem)),
+        },
+        (None, Some(duration)) => match parse_duration(&duration.value) {
+            Some(duration) => duration,
+            None => return Err(format!("{}: invalid duration {}.", summary, duration.value)),
+        },
+        (None, None) => Duration::seconds(0),
+    };
+
+    let uid = match find(properties, "UID") {
+        Some(uid) => uid.value.clone(),
+        None => format!("{}-{}", summary, start.date_time),
+    };
+
+    let occurrence = if find(properties, "RECURRENCE-ID").is_some() {
+        start.zone.to_local(start.date_time, timezone)
+    } else if let Some(rule) = find(properties, "RRULE") {
+        if modified
+            .iter()
+            .any(|(modified_uid, modified_day)| *modified_uid == uid && *modified_day == day)
+        {
+            return Ok(None);
+        }
+        let excluded = excluded_dates(properties, start.zone, timezone);
+        occurrence_on(start, &rule.value, &excluded, day, timezone)
+            .map_err(|problem| format!("{}: {}", summary, problem))?
+    } else {
+        start.zone.to_local(start.date_time, timezone)
+    };
+
+    match occurrence {
+        Some(start) if timezone.day(start) == day => Ok(Some(CalendarEvent {
+            uid,
+            summary,
+            start,
+            end: start + duration,
+        })),
+        _ => Ok(None),
+    }
+}
+
+/// Returns the occurrence of a recurring event taking place on the given
+/// day of the timezone, if any. Only daily and weekly rules are
+/// supported.
+fn occurrence_on(
+    start: ZonedDateTime,
+    rule: &str,
+    excluded: &[NaiveDate],
+    day: NaiveDate,
+    timezone: Timezone,
+) -> Result<Option<DateTime<Local>>, String> {
+    let mut frequency = None;
+    let mut interval: i64 = 1;
+    let mut count = None;
+    let mut until = None;
+    let mut weekdays = Vec::new();
+
+    for part in rule.split(';') {
+        let (name, value) = match part.split_once('=') {
+            Some(name_value) => name_value,
+            None => continue,
+        };
+        match name {
+            "FREQ" => frequency = Some(value.to_string()),
+            "INTERVAL" => interval = value.parse().map_err(|_| "invalid interval.")?,
+            "COUNT" => count = Some(value.parse::<usize>().map_err(|_| "invalid count.")?),
+            "UNTIL" => {
+                until = parse_value(value, Zone::Utc)?
+                    .and_then(|until| until.zone.to_local(until.date_time, timezone))
+                    .or_else(|| {
+                        NaiveDate::parse_from_str(value, "%Y%m%d")
+                            .ok()
+                            .and_then(|date| timezone.at(date, NaiveTime::from_hms(23, 59, 59)))
+                    })
+            }
+            "BYDAY" => {
+                for weekday in value.split(',') {
+                    weekdays.push(parse_weekday(weekday).ok_or("unsupported weekday.")?);
+                }
+            }
+            "WKST" => {}
+            _ => return Err(format!("unsupported recurrence rule {}.", rule)),
+        }
+    }
+
+    let frequency = frequency.ok_or("recurrence rule without frequency.")?;
+    if interval < 1 {
+        return Err("invalid interval.".to_string());
+    }
+    let start_date = start.date_time.date();
+    if frequency == "WEEKLY" && weekdays.is_empty() {
+        weekdays.push(start_date.weekday());
+    }
+
+    let is_occurrence = |date: NaiveDate| -> Result<bool, String> {
+        let days = (date - start_date).num_days();
+        match frequency.as_str() {
+            "DAILY" => {
+                Ok(days % interval == 0 && (weekdays.is_empty() || weekdays.contains(&date.weekday())))
+            }
+            "WEEKLY" => {
+                let week_start = start_date - Duration::days(start_date.weekday().num_days_from_monday() as i64);
+                let weeks = (date - week_start).num_days() / 7;
+                Ok(weeks % interval == 0 && weekdays.contains(&date.weekday()))
+            }
+            _ => Err(format!("unsupported recurrence frequency {}.", frequency)),
+        }
+    };
+
+    // The occurrence may fall on another day in the event's time zone.
+    let day_start = match timezone.at(day, NaiveTime::from_hms(0, 0, 0)) {
+        Some(day_start) => day_start,
+        None => return Ok(None),
+    };
+    let last_date = start
+        .zone
+        .in_zone(day_start + Duration::days(1), timezone)
+        .date();
+
+    let mut occurrences = 0;
+    let mut date = start_date;
+    while date <= last_date {
+        if is_occurrence(date)? {
+            occurrences += 1;
+            if matches!(count, Some(count) if occurrences > count) {
+                return Ok(None);
+            }
+            let occurrence = start
+                .zone
+                .to_local(date.and_time(start.date_time.time()), timezone);
+            if let Some(occurrence) = occurrence {
+                if matches!(until, Some(until) if occurrence > until) {
+                    return Ok(None);
+                }
+                if timezone.day(occurrence) == day && !excluded.contains(&date) {
+                    return Ok(Some(occurrence));
+                }
+            }
+        }
+        date = date.succ();
+    }
+
+    Ok(None)
+}
+
+/// The dates excluded from a recurring event, in the event's time zone.
+fn excluded_dates(properties: &[Property], zone: Zone, timezone: Timezone) -> Vec<NaiveDate> {
+    let mut dates = Vec::new();
+    for property in properties.iter().filter(|property| property.name == "EXDATE") {
+        let exdate_zone = property_zone(property);
+        for value in property.value.split(',') {
+            if let Ok(Some(excluded)) = parse_value(value, exdate_zone) {
+                if let Some(excluded) = excluded.zone.to_local(excluded.date_time, timezone) {
+                    dates.push(zone.in_zone(excluded, timezone).date());
+                }
+            } else if let Ok(date) = NaiveDate::parse_from_str(value, "%Y%m%d") {
+                dates.push(date);
+            }
+        }
+    }
+    dates
+}
+
+/// Returns the properties of every component with the given name,
+/// leaving out those of the components nested in them.
+fn read_components(calendar: &str, name: &str) -> Vec<Vec<Property>> {
+    let mut components = Vec::new();
+    let mut current: Option<Vec<Property>> = None;
+    let mut depth = 0;
+
+    for line in unfold(calendar) {
+        let property = match parse_property(&line) {
+            Some(property) => property,
+            None => continue,
+        };
+        match (property.name.as_str(), current.as_mut()) {
+            ("BEGIN", None) if property.value.eq_ignore_ascii_case(name) => {
+                current = Some(Vec::new());
+                depth = 0;
+            }
+            ("BEGIN", Some(_)) => depth += 1,
+            ("END", Some(_)) if depth > 0 => depth -= 1,
+            ("END", Some(_)) => components.extend(current.take()),
+            (_, Some(properties)) if depth == 0 => properties.push(property),
+            _ => {}
+        }
+    }
+
+    components
+}
+
+fn find<'a>(properties: &'a [Property], name: &str) -> Option<&'a Property> {
+    properties.iter().find(|property| property.name == name)
+}
+
+/// Joins the content lines split over several lines.
+fn unfold(calendar: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in calendar.lines() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+fn parse_property(line: &str) -> Option<Property> {
+    // The value starts at the first colon not inside a quoted parameter.
+    let mut quoted = false;
+    let mut colon = None;
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ':' if !quoted => {
+                colon = Some(index);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let colon = colon?;
+
+    let mut parts = line[..colon].split(';');
+    let name = parts.next()?.to_ascii_uppercase();
+    let params = parts
+        .filter_map(|param| param.split_once('='))
+        .map(|(param, value)| (param.to_ascii_uppercase(), value.trim_matches('"').to_string()))
+        .collect();
+
+    Some(Property {
+        name,
+        params,
+        value: line[colon + 1..].to_string(),
+    })
+}
+
+fn property_zone(property: &Property) -> Zone {
+    match property.param("TZID") {
+        Some(tzid) => tzid.parse::<Tz>().map(Zone::Named).unwrap_or(Zone::Floating),
+        None => Zone::Floating,
+    }
+}
+
+/// Parses a date time property. Returns None for dates without time.
+fn parse_date_time(property: &Property) -> Result<Option<ZonedDateTime>, String> {
+    if property.param("VALUE") == Some("DATE") {
+        return Ok(None);
+    }
+    parse_value(&property.value, property_zone(property))
+}
+
+/// Parses a date time value, in the given time zone unless it is a UTC
+/// time. Returns None for dates without time.
+fn parse_value(value: &str, zone: Zone) -> Result<Option<ZonedDateTime>, String> {
+    if value.len() == 8 {
+        return Ok(None);
+    }
+    let (value, zone) = match value.strip_suffix('Z') {
+        Some(value) => (value, Zone::Utc),
+        None => (value, zone),
+    };
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .map(|date_time| Some(ZonedDateTime { date_time, zone }))
+        .map_err(|_| format!("invalid date time {}.", value))
+}
+
+/// Parses a duration value, like "PT1H30M".
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.strip_prefix('+').unwrap_or(value);
+    let value = value.strip_prefix('P')?;
+    let mut duration = Duration::seconds(0);
+    let mut number = String::new();
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            'W' | 'D' | 'H' | 'M' | 'S' => {
+                let amount: i64 = number.parse().ok()?;
+                number.clear();
+                duration = duration
+                    + match c {
+                        'W' => Duration::weeks(amount),
+                        'D' => Duration::days(amount),
+                        'H' => Duration::hours(amount),
+                        'M' => Duration::minutes(amount),
+                        _ => Duration::seconds(amount),
+                    };
+            }
+            _ => return None,
+        }
+    }
+    Some(duration)
+}
+
+fn parse_weekday(value: &str) -> Option<Weekday> {
+    // Ordinal weekdays (like "1MO") are not supported.
+    match value {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// Unescapes a text value.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') | Some('N') => unescaped.push('\n'),
+                Some(escaped) => unescaped.push(escaped),
+                None => {}
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+    unescaped
+}
@@ -2,18 +2,41 @@
 // - validating input..
 // - printing the output.
 //
-// All interactions with the data should be done via models.
+// All interactions with the data should be done via the journal.
 
-use crate::model;
-use crate::model::TaskExtra;
-use crate::model::TaskState;
-use crate::model::WorkState;
+use crate::cli::ImportFormat;
+use akiv::backlog;
+use akiv::clock::Timezone;
+use akiv::config::{parse_day, parse_time, CloseAt, CloseTask};
+use akiv::forecast;
+use akiv::hooks;
+use akiv::hooks::Event;
+use akiv::ics;
+use akiv::journal::UnclosedDay;
+use akiv::markdown;
+use akiv::model::{Task, TaskExtra, TaskState, WorkState};
+use akiv::plan;
+use akiv::pomodoro;
+use akiv::pomodoro::Step;
+use akiv::standup;
+use akiv::storage;
+use akiv::storage::Backend;
+use akiv::suggest;
+use akiv::timesheet;
+use akiv::timesheet::{Grouping, Rounding};
+use akiv::webhooks;
+use akiv::{Error, Journal};
 use anyhow::bail;
-use anyhow::Result;
-use chrono::{DateTime, Duration, Local};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime};
 use humantime::format_duration;
 use prettytable::{Row, Table};
-use rusqlite::Connection;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+/// The setting keeping the last day left running that was told about,
+/// when it could not be asked about.
+const LEFT_RUNNING_WARNED_SETTING: &str = "left-running-warned";
 
 /// Adds a task to the current day.
 ///
@@ -22,169 +45,451 @@ use rusqlite::Connection;
 ///   replaced by len(tasks) + 1, (that is, after the last one).  If the
 ///   'at' is zero or less, it will be replaced by 1 (the first task.)
 ///
+/// - If 'fixed_at' is defined, the task is an appointment: it is
+///   expected to start at that time, whatever its position.
+///
+/// - If 'estimated_duration' is not defined, the estimate suggested
+///   from similar finished tasks is used.
+///
 /// Adding a task does not set the current work state to "running".
 pub fn add_task(
-    db: Connection,
+    journal: &mut Journal,
     description: String,
-    estimated_duration: Duration,
+    estimated_duration: Option<Duration>,
     at: Option<u32>,
+    fixed_at: Option<NaiveTime>,
 ) -> Result<()> {
-    let tasks_count = model::tasks_count(&db)?;
-    let mut position = at.unwrap_or(tasks_count + 1);
+    let estimated_duration = match estimated_duration {
+        Some(estimated_duration) => estimated_duration,
+        None => {
+            let suggestion = journal.suggest(&description)?;
+            println!(
+                "Estimate from {} similar tasks: {}",
+                suggestion.samples,
+                format_chrono_duration(suggestion.estimate)
+            );
+            suggestion.estimate
+        }
+    };
 
-    // automatically correct position if its out of bounds.
-    if position > tasks_count {
-        position = tasks_count + 1;
-    }
+    let task = journal.add(&description, estimated_duration, at, fixed_at)?;
+    print_task(&task);
+    hooks::run(journal, Event::Add, Some(&task), None);
+    Ok(())
+}
 
-    if position < 1 {
-        position = 1;
-    }
+/// Prints the estimate suggested for a task with the given
+/// description, and the similar finished tasks it is learned from.
+pub fn suggest(journal: &Journal, description: String) -> Result<()> {
+    let finished_tasks = journal.finished_tasks(None, None)?;
+    let similar_tasks = suggest::similar_tasks(&description, &finished_tasks);
+    let suggestion = suggest::suggest(&similar_tasks).ok_or(Error::NoSimilarTasks)?;
 
-    model::add_task(&db, position, &description, estimated_duration)?;
+    let mut table = Table::new();
+    table.add_row(row!["day", "task", "exp. duration", "ellapsed"]);
+    for (task, ellapsed) in similar_tasks.iter().take(10) {
+        table.add_row(row![
+            task.day,
+            textwrap::fill(&task.description, 38),
+            format_chrono_duration(task.estimated_duration),
+            format_chrono_duration(*ellapsed)
+        ]);
+    }
+    table.printstd();
 
     println!(
-        "{}. {} ({})",
-        position,
-        &description,
-        format_chrono_duration(estimated_duration)
+        "Suggested estimate: {} ({} similar tasks, half of them took between {} and {}).",
+        format_chrono_duration(suggestion.estimate),
+        suggestion.samples,
+        format_chrono_duration(suggestion.spread.0),
+        format_chrono_duration(suggestion.spread.1)
     );
-    //list(journal_path);
     Ok(())
 }
 
-/// Finishes the current task and starts the next, if any. The full
-/// behavior of 'next' is described as follows:
-///
-/// 0. If there is no active task, and work state is "stopped", AND there
-///    are not started tasks, if starts the next not started one.
-///
-/// 1. If the work state is "stopped", nothing happens.
-///
-/// 2. The active tasks is finished.
-///
-/// 3. If there are not started tasks, starts the next one.
-pub fn next(db: Connection) -> Result<()> {
-    let state = model::current_work_state(&db)?;
-    let currently_running_task_option = model::active_task(&db)?;
+/// Finishes the current task and starts the next, if any. See
+/// 'Journal::next'.
+pub fn next(journal: &mut Journal) -> Result<()> {
+    let next = journal.next()?;
+    hooks::run(
+        journal,
+        Event::Next,
+        next.started.as_ref(),
+        next.finished.as_ref(),
+    );
+    print_waiting(journal)
+}
 
-    if matches!(state, WorkState::Stopped) {
+/// Prints the details of the task at the given position: its times,
+/// its note, and the git repository it was worked in.
+pub fn show(journal: &Journal, position: u32) -> Result<()> {
+    let day = journal.day(journal.today()?)?;
+    let day_task = day
+        .tasks
+        .into_iter()
+        .find(|day_task| day_task.task.position == position)
+        .ok_or(Error::NoSuchTask(position))?;
+    let task = &day_task.task;
+    let timezone = journal.timezone();
 
-        // If its stopped and there are no active tasks, start the next.
-        // This happens either at the beginning of the day or after a task
-        // was added after all tasks have been completed.
-        if currently_running_task_option.is_none() {
-            let first_not_started_task_option = model::first_not_started_task(&db)?;
-            if let Some(first_not_started_task) = first_not_started_task_option {
-                model::switch_work_state(&db)?;
-                model::start_task(&db, first_not_started_task.position)?;
-                return Ok(())
+    println!("{}. {}", task.position, task.description);
+    let state = match task.state() {
+        TaskState::Done => "done",
+        TaskState::Active => "active",
+        TaskState::Pending => "pending",
+    };
+    println!("State:      {}", state);
+    if let Some(fixed_at) = task.fixed_at {
+        println!("At:         {}", fixed_at.format("%H:%M"));
+    }
+    println!(
+        "Estimated:  {}",
+        format_chrono_duration(task.estimated_duration)
+    );
+    println!("Worked:     {}", format_chrono_duration(day_task.ellapsed));
+    if task.pomodoros > 0 {
+        println!("Pomodoros:  {}", task.pomodoros);
+    }
+    println!(
+        "Started:    {}",
+        format_optional_time(timezone, task.started_at, "-".to_string())
+    );
+    println!(
+        "Finished:   {}",
+        format_optional_time(timezone, task.finished_at, "-".to_string())
+    );
+    if let Some(git) = &task.git {
+        println!("Repository: {}", git.repository);
+        println!(
+            "Branch:     {}",
+            git.branch.as_deref().unwrap_or("(detached)")
+        );
+        println!("HEAD:       {}", git.head);
+        for (index, commit) in git.commits.iter().enumerate() {
+            if index == 0 {
+                println!("Commits:    {}", commit);
+            } else {
+                println!("            {}", commit);
             }
         }
-        bail!("Work is stopped. Use 'akiv start' before moving to next task.");
     }
+    if let Some(note) = &task.note {
+        println!();
+        println!("{}", note);
+    }
+    Ok(())
+}
 
-    // At this point there should be an active task.
-    let currently_running_task = currently_running_task_option.unwrap();
-
-    // Stop the currently running task:
-    model::finish_task(&db, currently_running_task.position)?;
-    // Start the next task if any:
-    model::start_task(&db, currently_running_task.position + 1)?;
+/// Adds a line to the note of the task at the given position.
+pub fn note(journal: &mut Journal, position: u32, text: String) -> Result<()> {
+    journal.note(position, text.trim())?;
+    Ok(())
+}
 
-    // Stop work if there are no tasks left.
-    let unfinished_tasks_count = model::unfinished_tasks_count(&db)?;
-    if unfinished_tasks_count == 0 {
-        model::switch_work_state(&db)?;
-    }
+/// Marks the task at the given position as done, whatever its state.
+/// See 'Journal::done'.
+pub fn done(journal: &mut Journal, position: u32, duration: Option<Duration>) -> Result<()> {
+    let task = journal.done(position, duration)?;
+    println!("{}. {} (done)", task.position, &task.description);
+    let active_task = journal.active_task()?;
+    hooks::run(journal, Event::Done, active_task.as_ref(), Some(&task));
+    Ok(())
+}
 
+/// Reopens the done task at the given position, making it the active
+/// task again. The work state is not changed.
+pub fn reopen(journal: &mut Journal, position: u32) -> Result<()> {
+    let task = journal.reopen(position)?;
+    println!("{}. {} (reopened)", task.position, &task.description);
+    hooks::run(journal, Event::Reopen, Some(&task), None);
     Ok(())
 }
 
 /// Removes the task at the given position.
 ///
 /// - Only not started tasks can be removed.
-pub fn remove_task(db: Connection, position: u32) -> Result<()> {
-    let tasks_count = model::tasks_count(&db)?;
-    let first_not_started_task = model::first_not_started_task(&db)?;
+pub fn remove_task(journal: &mut Journal, position: u32) -> Result<()> {
+    let task = journal.remove(position)?;
+    hooks::run(journal, Event::Remove, Some(&task), None);
+    Ok(())
+}
 
-    if first_not_started_task.is_none() {
-        bail!("You have no tasks to remove!")
-    }
+/// Moves the task at the given position to another one.
+pub fn move_task(journal: &mut Journal, from: u32, to: u32) -> Result<()> {
+    let task = journal.move_task(from, to)?;
+    println!("{}. {}", task.position, &task.description);
+    Ok(())
+}
 
-    if position < first_not_started_task.unwrap().position {
-        bail!("You can only remove non started tasks.")
-    }
+/// Set the current work state to running. It also starts a task if none is
+/// running.
+pub fn start(journal: &mut Journal) -> Result<()> {
+    let started = journal.start()?;
+    println!("Started!");
+    let task = match started {
+        Some(task) => Some(task),
+        None => journal.active_task()?,
+    };
+    hooks::run(journal, Event::Start, task.as_ref(), None);
+    print_waiting(journal)
+}
 
-    if position > tasks_count {
-        bail!("Unexisting task.")
+/// Tells when the next appointment starts, if work is running with no
+/// active task because only appointments are left.
+fn print_waiting(journal: &Journal) -> Result<()> {
+    if journal.active_task()?.is_some() || matches!(journal.work_state()?, WorkState::Stopped) {
+        return Ok(());
     }
+    let appointment = journal
+        .tasks(journal.today()?)?
+        .into_iter()
+        .find(|task| task.started_at.is_none());
+    if let Some(task) = appointment {
+        if let Some(start) = plan::appointment_start(&task, journal.timezone()) {
+            println!(
+                "Waiting for appointment {} ({}) at {}.",
+                task.position,
+                task.description,
+                journal.timezone().localize(start).format("%H:%M")
+            );
+        }
+    }
+    Ok(())
+}
 
-    model::remove_task(&db, position)?;
-
+/// Set the current work state to stopped.
+///
+pub fn stop(journal: &mut Journal) -> Result<()> {
+    journal.stop()?;
+    println!("Pause!");
+    let task = journal.active_task()?;
+    hooks::run(journal, Event::Stop, task.as_ref(), None);
     Ok(())
 }
 
-/// Set the current work state to running. It also starts a task if none is
-/// running.
-pub fn start(db: Connection) -> Result<()> {
-    match model::current_work_state(&db)? {
-        WorkState::Running => bail!("You are already working!"),
-
-        WorkState::Stopped => {
-            let unfinished_tasks_count = model::unfinished_tasks_count(&db)?;
-            if unfinished_tasks_count == 0 {
-                bail!("There are no tasks to work on!");
+/// Works on the active task in pomodoros: work intervals separated by
+/// short breaks, with a long break after every fourth one. Work is
+/// started if stopped, and stopped during the breaks, as with 'start'
+/// and 'stop'. An interval is only counted if the same task was worked
+/// on all along: when work is restarted or another task is started with
+/// 'next' meanwhile, a new interval begins. Runs until interrupted, or
+/// until work is stopped by another command during an interval, or
+/// started during a break.
+pub fn pomodoro(
+    open_journal: &dyn Fn() -> Result<Journal>,
+    work: Duration,
+    short_break: Duration,
+    long_break: Duration,
+) -> Result<()> {
+    let timezone = open_journal()?.timezone();
+    let schedule = pomodoro::Schedule {
+        work,
+        short_break,
+        long_break,
+    };
+    pomodoro::run(open_journal, schedule, &mut |step| match step {
+        Step::Working(task, until) => println!(
+            "Working on {}. {} until {}.",
+            task.position,
+            task.description,
+            timezone.localize(*until).format("%H:%M")
+        ),
+        Step::Break(task, until) => println!(
+            "\x07Pomodoro {} of {}. {} done. Break until {}.",
+            task.pomodoros,
+            task.position,
+            task.description,
+            timezone.localize(*until).format("%H:%M")
+        ),
+        Step::TaskChanged => println!("The task changed, starting the interval again."),
+        Step::WorkRestarted => println!("Work was restarted, starting the interval again."),
+        Step::WorkStopped => println!("Work was stopped."),
+        Step::StartedDuringBreak => println!("Work was started during the break."),
+    })
+}
+
+///
+/// Close the previous days left running: as configured, or asking the
+/// time and what to do with the active task. Returns whether any was
+/// closed.
+///
+pub fn close_days(journal: &mut Journal) -> Result<bool> {
+    let unclosed_days = journal.unclosed_days()?;
+    if unclosed_days.is_empty() {
+        return Ok(false);
+    }
+    let config = journal.config().unclosed_days.clone();
+    let interactive = std::io::stdin().is_terminal();
+
+    let timezone = journal.timezone();
+    let mut left_running = Vec::new();
+    let mut closed_any = false;
+    for day in unclosed_days {
+        let close_time = day.close_time(config.close_at);
+        let (at, close_task) = match (config.close_at, close_time) {
+            (CloseAt::Ask, _) | (_, None) if interactive => {
+                ask_closing(&day, close_time, timezone, config.active_task)?
             }
-            model::switch_work_state(&db)?;
+            (CloseAt::Ask, _) | (_, None) => {
+                left_running.push(day.day);
+                continue;
+            }
+            (_, Some(at)) => (at, config.active_task),
+        };
+        let closed = journal.close_day(&day, at, close_task)?;
+        println!(
+            "Closed {} at {}.",
+            day.day,
+            timezone.localize(at).format("%H:%M")
+        );
+        if let Some(task) = &closed.carried_over {
+            print!("Carried over: ");
+            print_task(task);
         }
-    }
-    // if no task has started yet, start the first task that is not running.
-    let currently_running_task = model::active_task(&db);
 
-    if currently_running_task?.is_none() {
-        let task_to_start = model::first_not_started_task(&db)?;
-        if task_to_start.is_some() {
-            model::start_task(&db, task_to_start.unwrap().position)?;
+        // The same events as stopping work and finishing the task.
+        if day.working {
+            hooks::run(journal, Event::Stop, closed.finished.as_ref(), None);
+        }
+        if let Some(task) = &closed.finished {
+            hooks::run(journal, Event::Done, None, Some(task));
         }
+        webhooks::closed(journal, at, closed.finished.as_ref(), day.working)?;
+        closed_any = true;
     }
-    println!("Started!");
-    Ok(())
+    if left_running.is_empty() {
+        journal.mark_days_closed()?;
+    } else {
+        warn_left_running(journal, &left_running)?;
+    }
+    Ok(closed_any)
 }
 
-/// Set the current work state to stopped.
-///
-pub fn stop(db: Connection) -> Result<()> {
-    match model::current_work_state(&db)? {
-        WorkState::Stopped => bail!("Not running."),
-        WorkState::Running => {
-            model::switch_work_state(&db)?;
-            println!("Pause!")
+/// Tells once about the days that could not be closed without asking,
+/// when not run in a terminal: they stay open until akiv is, so that
+/// scripts are not bothered with them after the first time.
+fn warn_left_running(journal: &mut Journal, days: &[NaiveDate]) -> Result<()> {
+    let setting = format!("{}@{}", LEFT_RUNNING_WARNED_SETTING, journal.context());
+    let warned_until = match journal.setting(&setting)? {
+        Some(day) => Some(parse_day(&day)?),
+        None => None,
+    };
+    let new_days: Vec<&NaiveDate> = days
+        .iter()
+        .filter(|day| !matches!(warned_until, Some(warned_until) if **day <= warned_until))
+        .collect();
+    if let Some(last_day) = new_days.last() {
+        for day in &new_days {
+            eprintln!("{} was left running.", day);
         }
+        eprintln!("Run akiv in a terminal to close it, or set 'close-at' and 'end-of-work'.");
+        journal.set_setting(&setting, &last_day.format("%Y-%m-%d").to_string())?;
     }
     Ok(())
 }
 
+/// Asks when to close a day left running, and whether to finish or carry
+/// over its active task.
+fn ask_closing(
+    day: &UnclosedDay,
+    close_time: Option<DateTime<Local>>,
+    timezone: Timezone,
+    default_task: CloseTask,
+) -> Result<(DateTime<Local>, CloseTask)> {
+    let mut left = Vec::new();
+    if day.working {
+        left.push("work was not stopped".to_string());
+    }
+    if let Some(task) = &day.active_task {
+        left.push(format!(
+            "task {} ({}) is still active",
+            task.position, task.description
+        ));
+    }
+    println!("{} was left running: {}.", day.day, left.join(", and "));
+
+    // Work left running has no recorded end: without an end of work,
+    // there is no default time.
+    let last_activity = timezone.localize(day.last_activity).format("%H:%M");
+    let question = match close_time {
+        Some(close_time) => format!(
+            "Close it at [{}]: ",
+            timezone.localize(close_time).format("%H:%M")
+        ),
+        None => format!("Close it at (after {}): ", last_activity),
+    };
+    let at = loop {
+        let answer = ask(&question)?;
+        if answer.is_empty() {
+            match close_time {
+                Some(close_time) => break close_time,
+                None => continue,
+            }
+        }
+        match parse_time(&answer).map(|time| timezone.at(day.day, time)) {
+            Ok(Some(at)) if at >= day.last_activity => break at,
+            Ok(_) => println!("The last activity was at {}.", last_activity),
+            Err(_) => println!("Invalid time '{}', expected HH:MM.", answer),
+        }
+    };
+
+    let close_task = match &day.active_task {
+        Some(task) => {
+            let default = match default_task {
+                CloseTask::Finish => "f",
+                CloseTask::CarryOver => "c",
+            };
+            loop {
+                let answer = ask(&format!(
+                    "Finish task {}, or carry it over to today? (f/c) [{}]: ",
+                    task.position, default
+                ))?;
+                match answer.as_str() {
+                    "" => break default_task,
+                    "f" => break CloseTask::Finish,
+                    "c" => break CloseTask::CarryOver,
+                    _ => {}
+                }
+            }
+        }
+        None => default_task,
+    };
+    Ok((at, close_task))
+}
+
+/// Prints a question, and reads the answer, trimmed. The answer is
+/// empty at the end of the input.
+fn ask(question: &str) -> Result<String> {
+    print!("{}", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .context("Failed to read the answer.")?;
+    Ok(answer.trim().to_string())
+}
+
 ///
 /// Print the list of pauses for the current day.
 ///
-pub fn pauses(db: Connection) -> Result<()> {
+pub fn pauses(journal: &Journal) -> Result<()> {
     let mut table = Table::new();
 
     table.add_row(row!["start", "end", "duration"]);
 
-    let stopped_ranges = model::stopped_ranges(&db)?;
+    let timezone = journal.timezone();
+    let stopped_ranges = journal.pauses(journal.today()?)?;
     for range in stopped_ranges {
         match range.1 {
             Some(end) => table.add_row(row![
-                range.0.format("%T"),
-                end.format("%T"),
+                timezone.localize(range.0).format("%T"),
+                timezone.localize(end).format("%T"),
                 format_duration((end - range.0).to_std().unwrap())
             ]),
             None => table.add_row(row![
-                range.0.format("%T"),
+                timezone.localize(range.0).format("%T"),
                 "-",
-                format_duration((Local::now() - range.0).to_std().unwrap())
+                format_duration((journal.now() - range.0).to_std().unwrap())
             ]),
         };
     }
@@ -196,33 +501,70 @@ pub fn pauses(db: Connection) -> Result<()> {
 ///
 /// List the daily plan!
 ///
-pub fn list(db: Connection) -> Result<()> {
-    let mut table = Table::new();
+/// - If 'with_forecast' is set, the expected end time is replaced by
+///   the forecast percentiles.
+///
+/// - If 'all_contexts' is set, the plan of every context is listed,
+///   under its name.
+pub fn list(journal: &mut Journal, with_forecast: bool, all_contexts: bool) -> Result<()> {
+    if !all_contexts {
+        return list_context(journal, with_forecast);
+    }
 
-    let current_time: DateTime<Local> = Local::now();
+    let current_context = journal.context().to_string();
+    for (index, context) in journal.contexts()?.iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+        println!("{}:", context);
+        journal.set_context(context)?;
+        list_context(journal, with_forecast)?;
+    }
+    journal.set_context(&current_context)?;
+    Ok(())
+}
 
-    let pauses = model::stopped_ranges(&db)?;
+/// List the daily plan of the current context.
+fn list_context(journal: &Journal, with_forecast: bool) -> Result<()> {
+    let mut table = Table::new();
 
-    let work_state = model::current_work_state(&db)?;
-    let tasks = model::tasks(&db)?;
-    let task_iter = tasks.iter();
+    let work_state = journal.work_state()?;
+    let timezone = journal.timezone();
+    let day = journal.day(journal.today()?)?;
+    let task_forecasts = if with_forecast {
+        Some(journal.forecast()?.tasks)
+    } else {
+        None
+    };
 
-    let mut unfinished_tasks_estimated_duration = Duration::seconds(0);
+    let mut header = row!["id", "task", "started at", "exp. duration", "ellapsed"];
+    match task_forecasts {
+        Some(_) => {
+            for percentile in forecast::PERCENTILES.iter() {
+                header.add_cell(cell!(format!("P{} end time", percentile)));
+            }
+        }
+        None => header.add_cell(cell!("exp. end time")),
+    }
+    header.add_cell(cell!("pause time"));
+    let with_pomodoros = day.tasks.iter().any(|day_task| day_task.task.pomodoros > 0);
+    if with_pomodoros {
+        header.add_cell(cell!("pomodoros"));
+    }
+    table.add_row(header);
 
-    table.add_row(row![
-        "id",
-        "task",
-        "started at",
-        "exp. duration",
-        "ellapsed",
-        "exp. end time",
-        "pause time"
-    ]);
- 
-    for task in task_iter {
-        let etime: Duration = model::ellapsed_time(task, &pauses)?;
+    for (index, day_task) in day.tasks.iter().enumerate() {
+        let task = &day_task.task;
+        let expected_end_time = format_optional_time(
+            timezone,
+            day_task
+                .projection
+                .as_ref()
+                .map(|projection| projection.end),
+            "DONE".to_string(),
+        );
 
-        table.add_row(Row::new(vec![
+        let mut row = Row::new(vec![
             cell!(task.position),
             match task.state() {
                 TaskState::Active => match work_state {
@@ -232,48 +574,565 @@ pub fn list(db: Connection) -> Result<()> {
                 TaskState::Done => cell!(Fg->textwrap::fill(&task.description, 38)),
                 TaskState::Pending => cell!(textwrap::fill(&task.description, 38)),
             },
-            cell!(format_optional_time(task.started_at, "".to_string())),
+            match (task.started_at, task.fixed_at) {
+                (None, Some(fixed_at)) => cell!(Fc->format!("@{}", fixed_at.format("%H:%M"))),
+                _ => cell!(format_optional_time(
+                    timezone,
+                    task.started_at,
+                    "".to_string()
+                )),
+            },
             cell!(format_chrono_duration(task.estimated_duration)),
-            if etime > task.estimated_duration {
-                cell!(FR->format_chrono_duration(etime))
+            if day_task.ellapsed > task.estimated_duration {
+                cell!(FR->format_chrono_duration(day_task.ellapsed))
             } else {
-                cell!(format_chrono_duration(etime))
+                cell!(format_chrono_duration(day_task.ellapsed))
             },
-            cell!(format_optional_time(
-                model::estimated_end_time(task, unfinished_tasks_estimated_duration, &pauses)?,
-                "DONE".to_string()
-            )),
-            cell!(format_chrono_duration(model::paused_time(task, &pauses)?)),
-        ]));
-
-        if task.finished_at.is_none() {
-            if task.started_at.is_none() {
-                unfinished_tasks_estimated_duration =
-                    unfinished_tasks_estimated_duration + task.estimated_duration;
-            } else {
-                let worked_time = (current_time - task.started_at.unwrap())
-                    - (model::paused_time(task, &pauses)?);
-                unfinished_tasks_estimated_duration = unfinished_tasks_estimated_duration
-                    + std::cmp::max(task.estimated_duration - worked_time, Duration::seconds(0));
+        ]);
+        match &task_forecasts {
+            Some(task_forecasts) => match task_forecasts[index] {
+                Some(ends) => {
+                    for end in ends.iter() {
+                        row.add_cell(cell!(timezone.localize(*end).format("%T")));
+                    }
+                }
+                None => {
+                    for _ in forecast::PERCENTILES.iter() {
+                        row.add_cell(cell!("DONE"));
+                    }
+                }
+            },
+            None => row.add_cell(match &day_task.projection {
+                Some(projection) if projection.has_conflict() => cell!(FR->expected_end_time),
+                Some(_) if is_overrunning(task, &day.capacity) => cell!(FY->expected_end_time),
+                _ => cell!(expected_end_time),
+            }),
+        }
+        row.add_cell(cell!(format_chrono_duration(day_task.paused)));
+        if with_pomodoros {
+            row.add_cell(cell!(task.pomodoros));
+        }
+        table.add_row(row);
+    }
+
+    table.printstd();
+
+    for day_task in &day.tasks {
+        let task = &day_task.task;
+        if let Some(projection) = &day_task.projection {
+            if projection.late {
+                println!(
+                    "Appointment {} ({}) should have started at {}.",
+                    task.position,
+                    task.description,
+                    timezone.localize(projection.start).format("%H:%M")
+                );
+            }
+            for position in &projection.collisions {
+                if let Some(appointment) = day
+                    .tasks
+                    .iter()
+                    .map(|day_task| &day_task.task)
+                    .find(|task| task.position == *position)
+                {
+                    println!(
+                        "Task {} ({}) runs into appointment {} ({}) at {}.",
+                        task.position,
+                        task.description,
+                        appointment.position,
+                        appointment.description,
+                        appointment
+                            .fixed_at
+                            .map(|fixed_at| fixed_at.format("%H:%M").to_string())
+                            .unwrap_or_default()
+                    );
+                }
+            }
+        }
+    }
+
+    if let Some(capacity) = &day.capacity {
+        print_capacity(capacity, timezone);
+    }
+
+    if !day.tasks.is_empty() && day.tasks.iter().all(|day_task| day_task.task.started_at.is_none()) {
+        println!("You have not yet started your work for the day. Type 'akiv start'.");
+    }
+
+    Ok(())
+}
+
+///
+/// Set the end of work for the current day, if given, and print how the
+/// unfinished work fits before it.
+///
+pub fn plan(journal: &mut Journal, until: Option<NaiveTime>) -> Result<()> {
+    if let Some(until) = until {
+        journal.set_end_of_work(until)?;
+    }
+
+    match journal.day(journal.today()?)?.capacity {
+        Some(capacity) => print_capacity(&capacity, journal.timezone()),
+        None => bail!(
+            "No end of work is planned. Use 'akiv plan --until HH:MM' or set 'end-of-work' in the config file."
+        ),
+    }
+    Ok(())
+}
+
+/// Plans a break for the current day.
+pub fn add_break(journal: &mut Journal, start: NaiveTime, duration: Duration) -> Result<()> {
+    journal.add_break(start, duration)?;
+    println!(
+        "Break at {} ({})",
+        start.format("%H:%M"),
+        format_chrono_duration(duration)
+    );
+    Ok(())
+}
+
+/// Removes a break planned for the current day.
+pub fn remove_break(journal: &mut Journal, id: u32) -> Result<()> {
+    journal.remove_break(id)?;
+    Ok(())
+}
+
+///
+/// Print the breaks planned for the current day, including the daily
+/// ones from the config file.
+///
+pub fn breaks_list(journal: &Journal) -> Result<()> {
+    let mut table = Table::new();
+
+    table.add_row(row!["id", "start", "end", "duration"]);
+
+    let mut rows: Vec<(String, NaiveTime, Duration)> = journal
+        .planned_breaks()?
+        .into_iter()
+        .map(|planned_break| {
+            (
+                planned_break.id.to_string(),
+                planned_break.start,
+                planned_break.duration,
+            )
+        })
+        .collect();
+    rows.extend(
+        journal
+            .config()
+            .breaks
+            .iter()
+            .map(|daily_break| ("daily".to_string(), daily_break.start, daily_break.duration)),
+    );
+    rows.sort_by_key(|row| row.1);
+
+    for (id, start, duration) in rows {
+        table.add_row(row![
+            id,
+            start.format("%H:%M"),
+            (start + duration).format("%H:%M"),
+            format_chrono_duration(duration)
+        ]);
+    }
+
+    table.printstd();
+    Ok(())
+}
+
+///
+/// Use the given context from now on.
+///
+pub fn use_context(journal: &mut Journal, name: String) -> Result<()> {
+    journal.use_context(&name)?;
+    println!("Using context {}.", name);
+    Ok(())
+}
+
+///
+/// List the contexts, marking the current one.
+///
+pub fn contexts_list(journal: &Journal) -> Result<()> {
+    for context in journal.contexts()? {
+        if context == journal.context() {
+            println!("* {}", context);
+        } else {
+            println!("  {}", context);
+        }
+    }
+    Ok(())
+}
+
+///
+/// Print the forecast of the end times of the unfinished tasks and of
+/// the day, based on how long past tasks took compared to their
+/// estimates.
+///
+pub fn forecast(journal: &Journal) -> Result<()> {
+    let forecast = journal.forecast()?;
+    let timezone = journal.timezone();
+    let tasks = journal.tasks(journal.today()?)?;
+
+    let mut table = Table::new();
+    let mut header = row!["id", "task"];
+    for percentile in forecast::PERCENTILES.iter() {
+        header.add_cell(cell!(format!("P{}", percentile)));
+    }
+    table.add_row(header);
+
+    for (task, ends) in tasks.iter().zip(forecast.tasks.iter()) {
+        if let Some(ends) = ends {
+            let mut row = row![task.position, textwrap::fill(&task.description, 38)];
+            for end in ends.iter() {
+                row.add_cell(cell!(timezone.localize(*end).format("%H:%M")));
             }
+            table.add_row(row);
+        }
+    }
+
+    if let Some(ends) = forecast.day {
+        let mut row = row!["", b->"day"];
+        for end in ends.iter() {
+            row.add_cell(cell!(b->timezone.localize(*end).format("%H:%M")));
         }
+        table.add_row(row);
     }
 
     table.printstd();
-     
-    let first_not_started_option = model::first_not_started_task(&db)?;
-    if let Some(first_not_started) = first_not_started_option {
-        if first_not_started.position == 1 {
-            println!("You have not yet started your work for the day. Type 'akiv start'.");
+    Ok(())
+}
+
+///
+/// Export the tasks of a day as an iCalendar file. Finished tasks are
+/// exported with their actual start and end, and, for the current day,
+/// the unfinished ones at their expected times.
+///
+pub fn export_ics(journal: &Journal, day: Option<NaiveDate>, output: Option<PathBuf>) -> Result<()> {
+    let day = match day {
+        Some(day) => day,
+        None => journal.today()?,
+    };
+
+    let mut events = Vec::new();
+    for day_task in journal.day(day)?.tasks {
+        let task = &day_task.task;
+        let (start, end) = match (task.started_at, task.finished_at, &day_task.projection) {
+            (Some(started_at), Some(finished_at), _) => (started_at, finished_at),
+            (Some(started_at), None, Some(projection)) => (started_at, projection.end),
+            (None, None, Some(projection)) => (projection.start, projection.end),
+            _ => continue,
+        };
+        events.push(ics::Event {
+            uid: task_uid(task),
+            summary: task.description.clone(),
+            description: format!(
+                "Estimated duration: {}. Ellapsed: {}.",
+                format_chrono_duration(task.estimated_duration),
+                format_chrono_duration(day_task.ellapsed)
+            ),
+            start,
+            end,
+            tentative: task.started_at.is_none(),
+        });
+    }
+
+    write_output(output, &ics::write_calendar(&events, journal.now()))
+}
+
+///
+/// Export the tasks of a day as a Markdown checklist, with the time
+/// worked on each of them, their notes, and the pauses.
+///
+pub fn export_markdown(
+    journal: &Journal,
+    day: Option<NaiveDate>,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let day = match day {
+        Some(day) => day,
+        None => journal.today()?,
+    };
+    let log = markdown::write_day_log(&journal.day(day)?, journal.timezone());
+    write_output(output, &log)
+}
+
+///
+/// Import tasks for the current day from a file, in the given format or
+/// the one guessed from its extension.
+///
+pub fn import(
+    journal: &mut Journal,
+    file: PathBuf,
+    format_file: Option<PathBuf>,
+    format: Option<ImportFormat>,
+) -> Result<()> {
+    // The format can also come first, as in "akiv import ics today.ics".
+    let (file, format) = match format_file {
+        Some(format_file) => {
+            if format.is_some() {
+                bail!("The format is given twice.");
+            }
+            let name = file.to_string_lossy();
+            (format_file, Some(name.parse::<ImportFormat>()?))
         }
+        None => (file, format),
+    };
+    let format = match format.or_else(|| ImportFormat::from_extension(&file)) {
+        Some(format) => format,
+        None => bail!(
+            "Unknown format of {}, use --format to set it.",
+            file.display()
+        ),
+    };
+    let content = std::fs::read_to_string(&file)
+        .with_context(|| format!("Failed to read {}.", file.display()))?;
+
+    match format {
+        ImportFormat::Ics => import_ics(journal, &content),
+        ImportFormat::TodoTxt => import_backlog(journal, backlog::read_todotxt(&content)),
+        ImportFormat::Markdown => import_backlog(journal, backlog::read_markdown(&content)),
+        ImportFormat::Taskwarrior => import_backlog(journal, backlog::read_taskwarrior(&content)),
     }
+}
 
+///
+/// Copy the journal at 'from' to a new journal at 'to', with the given
+/// backends or the ones guessed from the paths.
+///
+pub fn convert(
+    journal: &Journal,
+    from: &Path,
+    from_backend: Option<Backend>,
+    to: &Path,
+    to_backend: Option<Backend>,
+) -> Result<()> {
+    if !from.exists() {
+        bail!("{} does not exist.", from.display());
+    }
+    if to.exists() {
+        bail!("{} already exists.", to.display());
+    }
+    let source = storage::open(from, from_backend)?;
+    let mut destination = storage::open(to, to_backend)?;
+    let days = storage::copy(source.as_ref(), destination.as_mut(), journal.today()?)?;
+    println!("Copied {} days to {}.", days, to.display());
     Ok(())
 }
 
-fn format_optional_time(optional_timestamp: Option<DateTime<Local>>, default: String) -> String {
+///
+/// Merge the journal at the given path, with the backend guessed from
+/// it, into this one, and report what was added and the conflicts.
+///
+pub fn merge(journal: &mut Journal, file: &Path) -> Result<()> {
+    let other = storage::open_read_only(file, None)?;
+    let merge = journal.merge(other.as_ref())?;
+
+    for (task, other_position) in &merge.added {
+        if task.position == *other_position {
+            println!("Added {} {}. {}", task.day, task.position, task.description);
+        } else {
+            println!(
+                "Added {} {}. {} (at {} in the other journal)",
+                task.day, task.position, task.description, other_position
+            );
+        }
+    }
+    println!(
+        "{} tasks added, {} already here.",
+        merge.added.len(),
+        merge.unchanged
+    );
+
+    if !merge.conflicts.is_empty() {
+        println!("\nConflicts, left as they are here:");
+        for conflict in &merge.conflicts {
+            println!("  - {}", conflict);
+        }
+    }
+    Ok(())
+}
+
+/// Import the events of the current day from an iCalendar file, as
+/// appointments. Events imported before are skipped.
+fn import_ics(journal: &mut Journal, calendar: &str) -> Result<()> {
+    let (events, problems) = ics::read_events(calendar, journal.today()?, journal.timezone());
+
+    let mut skipped = 0;
+    for event in events {
+        let duration = std::cmp::max(Duration::seconds(0), event.end - event.start);
+        match journal.import(
+            Some(&event.uid),
+            &event.summary,
+            duration,
+            Some(journal.timezone().localize(event.start).time()),
+        )? {
+            Some(task) => print_task(&task),
+            None => skipped += 1,
+        }
+    }
+
+    if skipped > 0 {
+        println!("Skipped {} events already imported.", skipped);
+    }
+    for problem in problems {
+        println!("Could not import: {}", problem);
+    }
+    Ok(())
+}
+
+/// Import the pending tasks of a backlog at the end of the current
+/// day. Tasks without an estimate get the one suggested from similar
+/// finished tasks, if any, and tasks imported before are skipped.
+fn import_backlog(journal: &mut Journal, backlog: backlog::Backlog) -> Result<()> {
+    let finished_tasks = journal.finished_tasks(None, None)?;
+    let mut problems = backlog.problems;
+    let mut skipped = 0;
+
+    for item in backlog.items {
+        let estimate = match item.estimate.or_else(|| {
+            suggest::suggest(&suggest::similar_tasks(&item.description, &finished_tasks))
+                .map(|suggestion| suggestion.estimate)
+        }) {
+            Some(estimate) => estimate,
+            None => {
+                problems.push(format!("{}: no estimate.", item.description));
+                continue;
+            }
+        };
+
+        match journal.import(item.uid.as_deref(), &item.description, estimate, None)? {
+            Some(task) => print_task(&task),
+            None => skipped += 1,
+        }
+    }
+
+    if backlog.done > 0 {
+        println!("Skipped {} done tasks.", backlog.done);
+    }
+    if skipped > 0 {
+        println!("Skipped {} tasks already imported.", skipped);
+    }
+    for problem in problems {
+        println!("Could not import: {}", problem);
+    }
+    Ok(())
+}
+
+///
+/// Prints the tasks finished on the last working day (or since the
+/// given day), and the tasks left for today, in plain text or
+/// Markdown.
+///
+pub fn standup(journal: &Journal, since: Option<NaiveDate>, markdown: bool) -> Result<()> {
+    print!("{}", standup::write_standup(&journal.standup(since)?, markdown));
+    Ok(())
+}
+
+///
+/// Export the time worked on the tasks finished between two days
+/// (included) as CSV. The rounding and its increment default to the
+/// configured ones.
+///
+#[allow(clippy::too_many_arguments)]
+pub fn timesheet(
+    journal: &Journal,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    grouping: Grouping,
+    rounding: Option<Rounding>,
+    increment: Option<u32>,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let to = match to {
+        Some(to) => to,
+        None => journal.today()?,
+    };
+    let from = from.unwrap_or(to);
+    if from > to {
+        bail!("The first day comes after the last one.");
+    }
+
+    let finished_tasks = journal.finished_tasks(Some(from), Some(to))?;
+    let config = journal.config();
+    let entries = timesheet::entries(
+        &finished_tasks,
+        grouping,
+        rounding.unwrap_or(config.timesheet.rounding),
+        Duration::minutes(increment.unwrap_or(config.timesheet.increment) as i64),
+    );
+
+    write_output(output, &timesheet::write_csv(&entries, config.timezone))
+}
+
+/// A stable, unique identifier of a task, kept by 'convert' and
+/// 'merge'.
+fn task_uid(task: &Task) -> String {
+    format!("{}@akiv", task.uuid)
+}
+
+/// Prints a task as added or imported, like "3. Write report (30m)".
+fn print_task(task: &Task) {
+    match task.fixed_at {
+        Some(fixed_at) => println!(
+            "{}. {} ({} at {})",
+            task.position,
+            &task.description,
+            format_chrono_duration(task.estimated_duration),
+            fixed_at.format("%H:%M")
+        ),
+        None => println!(
+            "{}. {} ({})",
+            task.position,
+            &task.description,
+            format_chrono_duration(task.estimated_duration)
+        ),
+    }
+}
+
+/// Writes the content to the given file, or to the standard output.
+fn write_output(output: Option<PathBuf>, content: &str) -> Result<()> {
+    match output {
+        Some(path) => std::fs::write(&path, content)
+            .with_context(|| format!("Failed to write {}.", path.display())),
+        None => {
+            print!("{}", content);
+            Ok(())
+        }
+    }
+}
+
+fn is_overrunning(task: &Task, capacity: &Option<plan::Capacity>) -> bool {
+    matches!(capacity, Some(capacity) if capacity.overrunning.contains(&task.position))
+}
+
+fn print_capacity(capacity: &plan::Capacity, timezone: Timezone) {
+    let end_of_work = timezone.localize(capacity.end_of_work);
+    println!(
+        "End of work at {}: {} left, {} of planned work.",
+        end_of_work.format("%H:%M"),
+        format_chrono_duration(capacity.available),
+        format_chrono_duration(capacity.planned)
+    );
+    if !capacity.overrunning.is_empty() {
+        let positions: Vec<String> = capacity
+            .overrunning
+            .iter()
+            .map(|position| position.to_string())
+            .collect();
+        println!(
+            "Tasks expected to end after {}: {}.",
+            end_of_work.format("%H:%M"),
+            positions.join(", ")
+        );
+    }
+}
+
+fn format_optional_time(
+    timezone: Timezone,
+    optional_timestamp: Option<DateTime<Local>>,
+    default: String,
+) -> String {
     match optional_timestamp {
-        Some(timestamp) => timestamp.format("%T").to_string(),
+        Some(timestamp) => timezone.localize(timestamp).format("%T").to_string(),
         None => default,
     }
 }
@@ -0,0 +1,896 @@
+// The journal of the daily plans: the tasks of each day and the work
+// done on them. Every operation on the plan goes through a 'Journal',
+// which checks that it is allowed before saving it.
+
+use crate::clock::{Clock, SystemClock, Timezone};
+use crate::config;
+use crate::config::{CloseAt, CloseTask, Config};
+use crate::error::{Error, Result};
+use crate::forecast;
+use crate::git;
+use crate::merge;
+use crate::merge::Merge;
+use crate::model;
+use crate::model::{Pauses, PlannedBreak, Task, TaskExtra, TaskState, WorkState};
+use crate::plan;
+use crate::plan::{Capacity, Projection};
+use crate::standup;
+use crate::standup::Standup;
+use crate::storage;
+use crate::storage::{Backend, MemoryStorage, NewTask, QueuedEvent, Storage};
+use crate::suggest;
+use crate::suggest::Suggestion;
+use anyhow::Context;
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime};
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+use uuid::Uuid;
+
+pub struct Journal {
+    storage: Box<dyn Storage>,
+    config: Config,
+    clock: Rc<dyn Clock>,
+    /// The context of the tasks and work the operations apply to.
+    context: String,
+}
+
+/// A task, with the times computed from the work done on it.
+#[derive(Debug)]
+pub struct DayTask {
+    pub task: Task,
+    /// The time worked on the task, pauses excluded.
+    pub ellapsed: Duration,
+    /// The time work was stopped while the task was active.
+    pub paused: Duration,
+    /// When the task is expected to start and end. Only the unfinished
+    /// tasks of the current day have one.
+    pub projection: Option<Projection>,
+}
+
+/// The tasks of a day, with their computed times.
+#[derive(Debug)]
+pub struct Day {
+    pub day: NaiveDate,
+    pub tasks: Vec<DayTask>,
+    pub pauses: Pauses,
+    /// How the unfinished work fits before the end of work, for the
+    /// current day if an end of work is set.
+    pub capacity: Option<Capacity>,
+}
+
+/// A day before the current one left running: work was not stopped,
+/// or a task was not finished.
+#[derive(Debug)]
+pub struct UnclosedDay {
+    pub day: NaiveDate,
+    pub working: bool,
+    pub active_task: Option<Task>,
+    /// The last time something was recorded that day.
+    pub last_activity: DateTime<Local>,
+    /// The end of work of the day, if one is set.
+    pub end_of_work: Option<DateTime<Local>>,
+}
+
+impl UnclosedDay {
+    /// The time to close the day at, if it is known without asking.
+    /// Work left running has no recorded end, so the last activity is
+    /// only used when work was stopped; otherwise the day ends at its
+    /// end of work, if set and not before the last activity.
+    pub fn close_time(&self, close_at: CloseAt) -> Option<DateTime<Local>> {
+        let end_of_work = self
+            .end_of_work
+            .filter(|end_of_work| *end_of_work >= self.last_activity);
+        let last_activity = if self.working {
+            None
+        } else {
+            Some(self.last_activity)
+        };
+        match close_at {
+            CloseAt::LastActivity => last_activity.or(end_of_work),
+            CloseAt::EndOfWork | CloseAt::Ask => end_of_work.or(last_activity),
+        }
+    }
+}
+
+/// The task finished by 'close_day', and the one added when it was
+/// carried over.
+#[derive(Debug)]
+pub struct Closed {
+    pub finished: Option<Task>,
+    pub carried_over: Option<Task>,
+}
+
+/// The tasks finished and started by 'next'.
+#[derive(Debug)]
+pub struct Next {
+    pub finished: Option<Task>,
+    pub started: Option<Task>,
+}
+
+impl Journal {
+    /// Opens the journal at the given path, creating it if it does not
+    /// exist. The backend is guessed from the path if not given.
+    pub fn open(path: &Path, backend: Option<Backend>, config: Config) -> Result<Journal> {
+        Journal::with_storage(storage::open(path, backend)?, config)
+    }
+
+    /// Opens a new journal kept in memory.
+    pub fn open_in_memory(config: Config) -> Result<Journal> {
+        Journal::with_storage(Box::new(MemoryStorage::default()), config)
+    }
+
+    /// Opens a journal kept in the given storage, in the context last
+    /// used.
+    pub fn with_storage(storage: Box<dyn Storage>, config: Config) -> Result<Journal> {
+        let context = storage
+            .setting(storage::CONTEXT_SETTING)?
+            .unwrap_or_else(|| storage::DEFAULT_CONTEXT.to_string());
+        Ok(Journal {
+            storage,
+            config,
+            clock: Rc::new(SystemClock),
+            context,
+        })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Takes the current time from the given clock, instead of the
+    /// system one.
+    pub fn set_clock(&mut self, clock: Rc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Waits for the given time to pass on the clock of the journal.
+    pub fn sleep(&self, duration: Duration) {
+        self.clock.sleep(duration)
+    }
+
+    /// The current time, with seconds precision.
+    pub fn now(&self) -> DateTime<Local> {
+        self.clock.now()
+    }
+
+    /// The context of the tasks and work the operations apply to.
+    pub fn context(&self) -> &str {
+        &self.context
+    }
+
+    /// Applies the next operations to the given context. Context names
+    /// are single words.
+    pub fn set_context(&mut self, context: &str) -> Result<()> {
+        if context.is_empty() || context.contains(char::is_whitespace) {
+            return Err(Error::InvalidContext(context.to_string()));
+        }
+        self.context = context.to_string();
+        Ok(())
+    }
+
+    /// Sets the context, and keeps it for the next times the journal
+    /// is opened.
+    pub fn use_context(&mut self, context: &str) -> Result<()> {
+        self.set_context(context)?;
+        Ok(self
+            .storage
+            .set_setting(self.today()?, storage::CONTEXT_SETTING, context)?)
+    }
+
+    /// The contexts with tasks or work, and the current one, by name.
+    pub fn contexts(&self) -> Result<Vec<String>> {
+        let mut contexts = self.storage.contexts()?;
+        if !contexts.contains(&self.context) {
+            contexts.push(self.context.clone());
+            contexts.sort();
+        }
+        Ok(contexts)
+    }
+
+    /// A setting kept in the journal, like the current context.
+    pub fn setting(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.storage.setting(key)?)
+    }
+
+    pub fn set_setting(&mut self, key: &str, value: &str) -> Result<()> {
+        Ok(self.storage.set_setting(self.today()?, key, value)?)
+    }
+
+    /// The events waiting to be sent to webhooks, in the order they
+    /// were queued.
+    pub fn queued_events(&self) -> Result<Vec<QueuedEvent>> {
+        Ok(self.storage.queued_events()?)
+    }
+
+    /// Queues an event to post to a webhook.
+    pub fn queue_event(&mut self, url: &str, body: &str) -> Result<()> {
+        Ok(self.storage.queue_event(self.today()?, url, body)?)
+    }
+
+    /// Removes an event sent, or not to be sent anymore.
+    pub fn remove_queued_event(&mut self, id: u32) -> Result<()> {
+        Ok(self.storage.remove_queued_event(self.today()?, id)?)
+    }
+
+    /// The timezone days are counted in.
+    pub fn timezone(&self) -> Timezone {
+        self.config.timezone
+    }
+
+    /// The current day, in the configured timezone.
+    pub fn today(&self) -> Result<NaiveDate> {
+        Ok(self.timezone().day(self.now()))
+    }
+
+    /// The tasks of the given day, by position.
+    pub fn tasks(&self, day: NaiveDate) -> Result<Vec<Task>> {
+        Ok(self.storage.tasks(&self.context, day)?)
+    }
+
+    /// The task of the current day at the given position.
+    pub fn task_at(&self, position: u32) -> Result<Task> {
+        self.tasks(self.today()?)?
+            .into_iter()
+            .find(|task| task.position == position)
+            .ok_or(Error::NoSuchTask(position))
+    }
+
+    /// The active task of the current day, if any.
+    pub fn active_task(&self) -> Result<Option<Task>> {
+        Ok(self
+            .tasks(self.today()?)?
+            .into_iter()
+            .find(|task| task.is_active()))
+    }
+
+    /// The finished tasks of the days between 'from' and 'to' (included,
+    /// when given), with the time worked on each of them.
+    pub fn finished_tasks(
+        &self,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Result<Vec<(Task, Duration)>> {
+        let mut pauses_by_day: HashMap<String, Pauses> = HashMap::new();
+        let mut tasks = Vec::new();
+        for task in self.storage.finished_tasks(&self.context, from, to)? {
+            if !pauses_by_day.contains_key(&task.day) {
+                let day = config::parse_day(&task.day)?;
+                pauses_by_day.insert(task.day.clone(), self.pauses(day)?);
+            }
+            let ellapsed = model::ellapsed_time(&task, &pauses_by_day[&task.day], self.now());
+            tasks.push((task, ellapsed));
+        }
+        Ok(tasks)
+    }
+
+    /// The last day before the given one with finished tasks, if any.
+    pub fn last_worked_day(&self, before: NaiveDate) -> Result<Option<NaiveDate>> {
+        match self.storage.finished_tasks(&self.context, None, Some(before.pred()))?.last() {
+            Some(task) => Ok(Some(config::parse_day(&task.day)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The starts and stops of work of the given day.
+    pub fn work_events(&self, day: NaiveDate) -> Result<Vec<DateTime<Local>>> {
+        Ok(self.storage.work_events(&self.context, day)?)
+    }
+
+    /// The ranges where work was stopped during the given day.
+    pub fn pauses(&self, day: NaiveDate) -> Result<Pauses> {
+        Ok(model::stopped_ranges(&self.storage.work_events(&self.context, day)?))
+    }
+
+    /// Whether the user is working. Work is running after every odd
+    /// start or stop of the current day.
+    pub fn work_state(&self) -> Result<WorkState> {
+        if self.storage.work_events(&self.context, self.today()?)?.len() % 2 != 0 {
+            Ok(WorkState::Running)
+        } else {
+            Ok(WorkState::Stopped)
+        }
+    }
+
+    /// The tasks of the given day, with the time worked on them and,
+    /// for the current day, when the unfinished ones are expected to
+    /// end.
+    pub fn day(&self, day: NaiveDate) -> Result<Day> {
+        // The same time for every task, so that they add up.
+        let now = self.now();
+        let tasks = self.tasks(day)?;
+        let pauses = self.pauses(day)?;
+
+        let (projections, capacity) = if day == self.today()? {
+            let breaks = self.breaks()?;
+            let projections = plan::project(&tasks, &pauses, &breaks, now, self.timezone());
+            let capacity = self.end_of_work()?.map(|end_of_work| {
+                plan::capacity(&tasks, &pauses, &breaks, &projections, end_of_work, now)
+            });
+            (projections, capacity)
+        } else {
+            (tasks.iter().map(|_| None).collect(), None)
+        };
+
+        let mut day_tasks = Vec::new();
+        for (task, projection) in tasks.into_iter().zip(projections) {
+            day_tasks.push(DayTask {
+                ellapsed: model::ellapsed_time(&task, &pauses, now),
+                paused: model::paused_time(&task, &pauses, now),
+                task,
+                projection,
+            });
+        }
+
+        Ok(Day {
+            day,
+            tasks: day_tasks,
+            pauses,
+            capacity,
+        })
+    }
+
+    /// Adds a task to the current day, at the given position or after
+    /// the last one. Positions out of bounds are corrected. If
+    /// 'fixed_at' is given, the task is an appointment: it is expected
+    /// to start at that time, whatever its position.
+    pub fn add(
+        &mut self,
+        description: &str,
+        estimated_duration: Duration,
+        at: Option<u32>,
+        fixed_at: Option<NaiveTime>,
+    ) -> Result<Task> {
+        let tasks_count = self.tasks_count()?;
+        let position = at.unwrap_or(tasks_count + 1).clamp(1, tasks_count + 1);
+        self.insert_task(position, description, estimated_duration, fixed_at, None)?;
+        self.task_at(position)
+    }
+
+    /// Adds a task imported from elsewhere after the last one of the
+    /// current day, unless it was already imported.
+    pub fn import(
+        &mut self,
+        source_uid: Option<&str>,
+        description: &str,
+        estimated_duration: Duration,
+        fixed_at: Option<NaiveTime>,
+    ) -> Result<Option<Task>> {
+        if let Some(source_uid) = source_uid {
+            let tasks = self.tasks(self.today()?)?;
+            if tasks
+                .iter()
+                .any(|task| task.source_uid.as_deref() == Some(source_uid))
+            {
+                return Ok(None);
+            }
+        }
+        let position = self.tasks_count()? + 1;
+        self.insert_task(
+            position,
+            description,
+            estimated_duration,
+            fixed_at,
+            source_uid,
+        )?;
+        Ok(Some(self.task_at(position)?))
+    }
+
+    /// Removes the task at the given position. Only not started tasks
+    /// can be removed.
+    pub fn remove(&mut self, position: u32) -> Result<Task> {
+        let task = self.task_at(position)?;
+        if !matches!(task.state(), TaskState::Pending) {
+            return Err(Error::AlreadyStarted(position));
+        }
+        self.storage.remove_task(&self.context, self.today()?, position)?;
+        Ok(task)
+    }
+
+    /// Moves the task at position 'from' to position 'to', or to the
+    /// last position if 'to' is larger. Only not started tasks can be
+    /// moved.
+    pub fn move_task(&mut self, from: u32, to: u32) -> Result<Task> {
+        if !matches!(self.task_at(from)?.state(), TaskState::Pending) {
+            return Err(Error::MoveStarted(from));
+        }
+        let to = to.clamp(1, self.tasks_count()?);
+        self.storage.move_task(&self.context, self.today()?, from, to)?;
+        self.task_at(to)
+    }
+
+    /// Sets the work state to running, and starts the first pending
+    /// task if none is active. Returns the started task, if any.
+    pub fn start(&mut self) -> Result<Option<Task>> {
+        if matches!(self.work_state()?, WorkState::Running) {
+            return Err(Error::AlreadyWorking);
+        }
+        if self.unfinished_tasks_count()? == 0 {
+            return Err(Error::NoTasks);
+        }
+        self.switch_work_state()?;
+
+        if self.active_task()?.is_none() {
+            return self.start_first_pending_task();
+        }
+        Ok(None)
+    }
+
+    /// Sets the work state to stopped.
+    pub fn stop(&mut self) -> Result<()> {
+        if matches!(self.work_state()?, WorkState::Stopped) {
+            return Err(Error::NotWorking);
+        }
+        self.switch_work_state()?;
+        Ok(())
+    }
+
+    /// Finishes the active task and starts the first pending one, if
+    /// any:
+    ///
+    /// - If work is stopped and no task is active (at the beginning of
+    ///   the day, or after adding a task when all were done), work is
+    ///   started with the first pending task.
+    ///
+    /// - Otherwise, work must be running.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Next> {
+        let active_task = self.active_task()?;
+
+        if matches!(self.work_state()?, WorkState::Stopped) {
+            if active_task.is_none() {
+                if let Some(task) = self.first_not_started_task()? {
+                    self.switch_work_state()?;
+                    self.storage
+                        .set_started(&self.context, self.today()?, task.position, Some(self.now()))?;
+                    self.record_git_context(task.position)?;
+                    return Ok(Next {
+                        finished: None,
+                        started: Some(self.task_at(task.position)?),
+                    });
+                }
+            }
+            return Err(Error::WorkStopped);
+        }
+
+        let finished = match active_task {
+            Some(task) => {
+                self.storage
+                    .set_finished(&self.context, self.today()?, task.position, Some(self.now()))?;
+                self.record_git_commits(task.position)?;
+                Some(self.task_at(task.position)?)
+            }
+            None => None,
+        };
+        let started = self.start_first_pending_task()?;
+        Ok(Next { finished, started })
+    }
+
+    /// Marks the task at the given position as done, whatever its state.
+    ///
+    /// - If the task is the active one, it behaves like 'next'.
+    ///
+    /// - If the task was never started, it is recorded as having been
+    ///   worked on for 'duration' (zero by default) until now, pauses
+    ///   excluded.
+    pub fn done(&mut self, position: u32, duration: Option<Duration>) -> Result<Task> {
+        let task = self.task_at(position)?;
+
+        match task.state() {
+            TaskState::Done => return Err(Error::AlreadyDone(position)),
+            TaskState::Active => {
+                if duration.is_some() {
+                    return Err(Error::DurationOfStartedTask(position));
+                }
+                self.storage
+                    .set_finished(&self.context, self.today()?, position, Some(self.now()))?;
+                self.record_git_commits(position)?;
+                if matches!(self.work_state()?, WorkState::Running) {
+                    self.start_first_pending_task()?;
+                }
+            }
+            TaskState::Pending => {
+                let today = self.today()?;
+                let now = self.now();
+                let duration = duration.unwrap_or_else(Duration::zero);
+                // Started early enough for 'duration' to be worked, the
+                // pauses in between excluded.
+                let started_at = model::worked_since(duration, &self.pauses(today)?, now);
+                self.storage.set_started(&self.context, today, position, Some(started_at))?;
+                self.storage.set_finished(&self.context, today, position, Some(now))?;
+                // Stop work if there are no tasks left.
+                if matches!(self.work_state()?, WorkState::Running)
+                    && self.unfinished_tasks_count()? == 0
+                {
+                    self.switch_work_state()?;
+                }
+            }
+        }
+
+        self.task_at(position)
+    }
+
+    /// Reopens the done task at the given position, making it the
+    /// active task again, and keeping the time already worked on it.
+    /// Only one task can be active at a time.
+    pub fn reopen(&mut self, position: u32) -> Result<Task> {
+        let task = self.task_at(position)?;
+        if !task.is_done() {
+            return Err(Error::NotDone(position));
+        }
+        if let Some(active_task) = self.active_task()? {
+            return Err(Error::AnotherTaskActive(active_task.position));
+        }
+
+        // Move the start forward so that the time between finishing and
+        // reopening is not counted as worked, and the pauses since are
+        // not taken off twice.
+        let today = self.today()?;
+        let now = self.now();
+        let pauses = self.pauses(today)?;
+        let worked_time = model::ellapsed_time(&task, &pauses, now);
+        let started_at = model::worked_since(worked_time, &pauses, now);
+        self.storage
+            .set_started(&self.context, today, position, Some(started_at))?;
+        self.storage.set_finished(&self.context, today, position, None)?;
+        self.task_at(position)
+    }
+
+    /// Counts a pomodoro completed on the task at the given position.
+    pub fn add_pomodoro(&mut self, position: u32) -> Result<Task> {
+        let task = self.task_at(position)?;
+        self.storage
+            .set_pomodoros(&self.context, self.today()?, position, task.pomodoros + 1)?;
+        self.task_at(position)
+    }
+
+    /// Adds a line to the note of the task at the given position.
+    pub fn note(&mut self, position: u32, line: &str) -> Result<()> {
+        self.task_at(position)?;
+        self.storage.add_note(&self.context, self.today()?, position, line)?;
+        Ok(())
+    }
+
+    /// Merges another journal into this one: see 'merge::merge'.
+    pub fn merge(&mut self, other: &dyn Storage) -> Result<Merge> {
+        Ok(merge::merge(self.storage.as_mut(), other)?)
+    }
+
+    /// Suggests an estimate for a task, from the finished tasks with a
+    /// similar description.
+    pub fn suggest(&self, description: &str) -> Result<Suggestion> {
+        let finished_tasks = self.finished_tasks(None, None)?;
+        suggest::suggest(&suggest::similar_tasks(description, &finished_tasks))
+            .ok_or(Error::NoSimilarTasks)
+    }
+
+    /// The end of work of the current day: the planned one, or the
+    /// configured one.
+    pub fn end_of_work(&self) -> Result<Option<DateTime<Local>>> {
+        self.end_of_work_of(self.today()?)
+    }
+
+    fn end_of_work_of(&self, day: NaiveDate) -> Result<Option<DateTime<Local>>> {
+        Ok(self
+            .storage
+            .end_of_work(day)?
+            .or(self.config.end_of_work)
+            .and_then(|end_of_work| self.timezone().at(day, end_of_work)))
+    }
+
+    /// The days before the current one left running, oldest first. The
+    /// days already checked by 'mark_days_closed' are skipped.
+    pub fn unclosed_days(&self) -> Result<Vec<UnclosedDay>> {
+        let today = self.today()?;
+        let closed_until = match self.storage.setting(&self.closed_until_setting())? {
+            Some(day) => Some(config::parse_day(&day)?),
+            None => None,
+        };
+
+        let mut unclosed_days = Vec::new();
+        for day in self.storage.days()? {
+            if day >= today || matches!(closed_until, Some(closed_until) if day <= closed_until) {
+                continue;
+            }
+            let work_events = self.storage.work_events(&self.context, day)?;
+            let tasks = self.tasks(day)?;
+            let working = work_events.len() % 2 != 0;
+            let active_task = tasks.iter().find(|task| task.is_active()).cloned();
+            if !working && active_task.is_none() {
+                continue;
+            }
+            let task_times = tasks.iter().flat_map(|task| {
+                std::iter::once(task.created_at)
+                    .chain(task.started_at)
+                    .chain(task.finished_at)
+            });
+            let last_activity = work_events.iter().copied().chain(task_times).max();
+            if let Some(last_activity) = last_activity {
+                unclosed_days.push(UnclosedDay {
+                    day,
+                    working,
+                    active_task,
+                    last_activity,
+                    end_of_work: self.end_of_work_of(day)?,
+                });
+            }
+        }
+        Ok(unclosed_days)
+    }
+
+    /// Closes a day left running at the given time: work is stopped,
+    /// and the active task is finished. When carried over, it is also
+    /// added again at the top of the current day, for the time left of
+    /// its estimate, or the whole estimate if it ran over.
+    pub fn close_day(
+        &mut self,
+        day: &UnclosedDay,
+        at: DateTime<Local>,
+        close_task: CloseTask,
+    ) -> Result<Closed> {
+        if at < day.last_activity {
+            return Err(Error::CloseBeforeLastActivity);
+        }
+        if day.working {
+            self.storage.add_work_event(&self.context, day.day, at)?;
+        }
+        let task = match &day.active_task {
+            Some(task) => task,
+            None => {
+                return Ok(Closed {
+                    finished: None,
+                    carried_over: None,
+                })
+            }
+        };
+        self.storage
+            .set_finished(&self.context, day.day, task.position, Some(at))?;
+        let finished = self
+            .tasks(day.day)?
+            .into_iter()
+            .find(|finished| finished.position == task.position);
+        let carried_over = match close_task {
+            CloseTask::Finish => None,
+            CloseTask::CarryOver => {
+                let worked = model::ellapsed_time(task, &self.pauses(day.day)?, at);
+                let left = task.estimated_duration - worked;
+                let estimate = if left > Duration::zero() {
+                    left
+                } else {
+                    task.estimated_duration
+                };
+                self.insert_task(1, &task.description, estimate, task.fixed_at, None)?;
+                Some(self.task_at(1)?)
+            }
+        };
+        Ok(Closed {
+            finished,
+            carried_over,
+        })
+    }
+
+    /// Remembers that the days before the current one are closed, so
+    /// that they are not checked again.
+    pub fn mark_days_closed(&mut self) -> Result<()> {
+        let today = self.today()?;
+        let yesterday = today.pred().format("%Y-%m-%d").to_string();
+        Ok(self
+            .storage
+            .set_setting(today, &self.closed_until_setting(), &yesterday)?)
+    }
+
+    /// The setting keeping the last day checked by 'unclosed_days', for
+    /// the current context.
+    fn closed_until_setting(&self) -> String {
+        format!("closed-until@{}", self.context)
+    }
+
+    /// Plans the end of work of the current day.
+    pub fn set_end_of_work(&mut self, end_of_work: NaiveTime) -> Result<()> {
+        Ok(self.storage.set_end_of_work(self.today()?, end_of_work)?)
+    }
+
+    /// The breaks planned for the current day, by start time.
+    pub fn planned_breaks(&self) -> Result<Vec<PlannedBreak>> {
+        Ok(self.storage.planned_breaks(self.today()?)?)
+    }
+
+    /// Plans a break for the current day.
+    pub fn add_break(&mut self, start: NaiveTime, duration: Duration) -> Result<()> {
+        Ok(self
+            .storage
+            .add_planned_break(self.today()?, start, duration)?)
+    }
+
+    /// Removes a break planned for the current day.
+    pub fn remove_break(&mut self, id: u32) -> Result<()> {
+        if !self
+            .planned_breaks()?
+            .iter()
+            .any(|planned_break| planned_break.id == id)
+        {
+            return Err(Error::NoSuchBreak(id));
+        }
+        Ok(self.storage.remove_planned_break(self.today()?, id)?)
+    }
+
+    /// The breaks of the current day: the planned ones and the daily
+    /// ones.
+    pub fn breaks(&self) -> Result<plan::Breaks> {
+        let today = self.today()?;
+        let planned_breaks = self
+            .planned_breaks()?
+            .into_iter()
+            .map(|planned_break| (planned_break.start, planned_break.duration));
+        let daily_breaks = self
+            .config
+            .breaks
+            .iter()
+            .map(|daily_break| (daily_break.start, daily_break.duration));
+
+        let mut breaks = Vec::new();
+        for (start, duration) in planned_breaks.chain(daily_breaks) {
+            if let Some(start) = self.timezone().at(today, start) {
+                breaks.push((start, start + duration));
+            }
+        }
+        Ok(breaks)
+    }
+
+    /// The tasks finished since the given day, or since the last day
+    /// before today with finished tasks, and the tasks planned for today.
+    pub fn standup(&self, since: Option<NaiveDate>) -> Result<Standup> {
+        let today = self.today()?;
+        let last_worked_day = self.last_worked_day(today)?;
+        let done = match since.or(last_worked_day) {
+            Some(from) => self
+                .finished_tasks(Some(from), today.pred_opt())?
+                .into_iter()
+                .map(|(task, _)| task.description)
+                .collect(),
+            None => Vec::new(),
+        };
+        let planned = self
+            .tasks(today)?
+            .into_iter()
+            .filter(|task| !task.is_done())
+            .map(|task| match task.fixed_at {
+                Some(fixed_at) => format!("{} at {}", task.description, fixed_at.format("%H:%M")),
+                None => task.description,
+            })
+            .collect();
+        Ok(Standup {
+            title: standup::title(today, since, last_worked_day),
+            done,
+            planned,
+        })
+    }
+
+    /// Forecasts the end times of the tasks of the current day, from
+    /// how long the finished tasks took compared to their estimates.
+    pub fn forecast(&self) -> Result<forecast::Forecast> {
+        let ratios = forecast::ratios(&self.finished_tasks(None, None)?);
+        if ratios.len() < forecast::MIN_HISTORY {
+            return Err(Error::NotEnoughHistory {
+                found: ratios.len(),
+                needed: forecast::MIN_HISTORY,
+            });
+        }
+
+        let now = self.now();
+        let tasks = self.tasks(self.today()?)?;
+        let pauses = self.pauses(self.today()?)?;
+        let ellapsed_times: Vec<Duration> = tasks
+            .iter()
+            .map(|task| model::ellapsed_time(task, &pauses, now))
+            .collect();
+
+        Ok(forecast::forecast(
+            &tasks,
+            &ellapsed_times,
+            &self.breaks()?,
+            &ratios,
+            now,
+            self.timezone(),
+            &mut rand::thread_rng(),
+        ))
+    }
+
+    /// Starts the first pending task by position, if any, and returns
+    /// it. If there are no tasks left, it stops work. It supposes the
+    /// work state to be running.
+    fn start_first_pending_task(&mut self) -> Result<Option<Task>> {
+        let started = match self.first_not_started_task()? {
+            Some(task) => {
+                self.storage
+                    .set_started(&self.context, self.today()?, task.position, Some(self.now()))?;
+                self.record_git_context(task.position)?;
+                Some(self.task_at(task.position)?)
+            }
+            None => None,
+        };
+
+        // Stop work if there are no tasks left.
+        if self.unfinished_tasks_count()? == 0 {
+            self.switch_work_state()?;
+        }
+        Ok(started)
+    }
+
+    /// Records the git repository of the working directory on the task
+    /// at the given position, if enabled and in one.
+    fn record_git_context(&mut self, position: u32) -> Result<()> {
+        if !self.config.git_context {
+            return Ok(());
+        }
+        let dir = std::env::current_dir().context("Failed to read the working directory.")?;
+        if let Some(git_context) = git::capture(&dir) {
+            self.storage
+                .set_git(&self.context, self.today()?, position, Some(&git_context))?;
+        }
+        Ok(())
+    }
+
+    /// Records the commits made since the task at the given position was
+    /// started, if its git repository was recorded.
+    fn record_git_commits(&mut self, position: u32) -> Result<()> {
+        if let Some(mut git_context) = self.task_at(position)?.git {
+            if let Some(commits) = git::commits_since(&git_context) {
+                git_context.commits = commits;
+                self.storage
+                    .set_git(&self.context, self.today()?, position, Some(&git_context))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The first not started task of the current day, if any.
+    /// Appointments are skipped until their time has come.
+    fn first_not_started_task(&self) -> Result<Option<Task>> {
+        let now = self.now();
+        Ok(self.tasks(self.today()?)?.into_iter().find(|task| {
+            task.started_at.is_none()
+                && !matches!(plan::appointment_start(task, self.timezone()), Some(start) if start > now)
+        }))
+    }
+
+    fn tasks_count(&self) -> Result<u32> {
+        Ok(self.tasks(self.today()?)?.len() as u32)
+    }
+
+    /// The number of unfinished tasks of the current day, including the
+    /// active one.
+    fn unfinished_tasks_count(&self) -> Result<u32> {
+        Ok(self
+            .tasks(self.today()?)?
+            .iter()
+            .filter(|task| !task.is_done())
+            .count() as u32)
+    }
+
+    fn insert_task(
+        &mut self,
+        position: u32,
+        description: &str,
+        estimated_duration: Duration,
+        fixed_at: Option<NaiveTime>,
+        source_uid: Option<&str>,
+    ) -> Result<()> {
+        let task = NewTask {
+            uuid: Uuid::new_v4().to_string(),
+            description: description.to_string(),
+            estimated_duration,
+            fixed_at,
+            source_uid: source_uid.map(str::to_string),
+            created_at: self.now(),
+        };
+        Ok(self.storage.insert_task(&self.context, self.today()?, position, &task)?)
+    }
+
+    /// If work is running, stops it, and otherwise starts it.
+    fn switch_work_state(&mut self) -> Result<()> {
+        Ok(self
+            .storage
+            .add_work_event(&self.context, self.today()?, self.now())?)
+    }
+}
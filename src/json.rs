@@ -0,0 +1,30 @@
+// The JSON form of the tasks, the same in the HTTP API, the input of
+// the hooks and the events posted to the webhooks.
+
+use crate::model::{Task, TaskExtra, TaskState};
+use chrono::{DateTime, Local};
+use serde_json::{json, Value};
+
+pub fn task_json(task: &Task) -> Value {
+    json!({
+        "position": task.position,
+        "uuid": task.uuid,
+        "description": task.description,
+        "state": match task.state() {
+            TaskState::Done => "done",
+            TaskState::Active => "active",
+            TaskState::Pending => "pending",
+        },
+        "estimate": task.estimated_duration.num_seconds(),
+        "fixed_at": task.fixed_at.map(|fixed_at| fixed_at.format("%H:%M").to_string()),
+        "started_at": optional_time(task.started_at),
+        "finished_at": optional_time(task.finished_at),
+        "note": task.note,
+        "git": task.git,
+        "pomodoros": task.pomodoros,
+    })
+}
+
+pub fn optional_time(time: Option<DateTime<Local>>) -> Option<String> {
+    time.map(|time| time.to_rfc3339())
+}
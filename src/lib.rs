@@ -0,0 +1,29 @@
+// Akiv, the hyper minimalistic daily planner, as a library. The
+// 'Journal' keeps the tasks of each day and the work done on them; the
+// other modules compute plans and forecasts from it, read and write
+// the formats of other tools, and tell other tools about its changes.
+
+pub mod backlog;
+pub mod clock;
+pub mod config;
+pub mod error;
+pub mod forecast;
+pub mod git;
+pub mod hooks;
+pub mod ics;
+pub mod journal;
+pub mod json;
+pub mod markdown;
+pub mod merge;
+pub mod model;
+pub mod plan;
+pub mod pomodoro;
+pub mod server;
+pub mod standup;
+pub mod storage;
+pub mod suggest;
+pub mod timesheet;
+pub mod webhooks;
+
+pub use error::{Error, Result};
+pub use journal::Journal;
@@ -1,6 +1,7 @@
 #[macro_use]
 extern crate prettytable;
 
+use akiv::{clock, config, server, webhooks, Journal};
 use anyhow::anyhow;
 use directories::ProjectDirs;
 use std::path::PathBuf;
@@ -8,11 +9,8 @@ use structopt::StructOpt;
 
 mod cli;
 mod interface;
-mod model;
-use crate::model::init_journal;
-use rusqlite::Connection;
 
-use cli::{Command::*, CommandLineArgs};
+use cli::{BreakCommand, Command::*, CommandLineArgs, ContextCommand, ExportCommand};
 
 fn find_default_journal_file() -> Option<PathBuf> {
     if let Some(base_dirs) = ProjectDirs::from("com", "gozque", "akiv") {
@@ -28,15 +26,9 @@ fn find_default_journal_file() -> Option<PathBuf> {
     }
 }
 
-/// Get a connection to the journal database, creating it if it does
-/// not exist.
-pub fn get_journal_db(journal_path: PathBuf) -> anyhow::Result<Connection> {
-    let journal_exists = journal_path.exists();
-    let db = Connection::open(&journal_path)?;
-    if !journal_exists {
-        init_journal(&db)?;
-    }
-    Ok(db)
+fn find_default_config_file() -> Option<PathBuf> {
+    ProjectDirs::from("com", "gozque", "akiv")
+        .map(|base_dirs| base_dirs.config_dir().join("config.toml"))
 }
 
 fn main() -> anyhow::Result<()> {
@@ -44,14 +36,41 @@ fn main() -> anyhow::Result<()> {
     let CommandLineArgs {
         action,
         journal_file,
+        backend,
+        context,
+        config_file,
     } = CommandLineArgs::from_args();
 
+    let config = config::load(config_file.or_else(find_default_config_file).as_deref())?;
+
     // Unpack the journal file.
     let journal_file = journal_file
         .or_else(find_default_journal_file)
         .ok_or(anyhow!("Failed to find journal file."))?;
 
-    let database = get_journal_db(journal_file)?;
+    let clock = clock::from_env()?;
+    let open_journal = || -> anyhow::Result<Journal> {
+        let mut journal = Journal::open(&journal_file, backend, config.clone())?;
+        journal.set_clock(clock.clone());
+        if let Some(context) = &context {
+            journal.set_context(context)?;
+        }
+        Ok(journal)
+    };
+    let mut journal = open_journal()?;
+    let closed_days = if matches!(action, Convert { .. }) {
+        false
+    } else {
+        interface::close_days(&mut journal)?
+    };
+    // Webhooks are only notified of the changes, so that reading the
+    // journal never waits for them.
+    let changes_journal = action.changes_journal() || closed_days;
+    let state = if changes_journal {
+        webhooks::state(&journal)?
+    } else {
+        None
+    };
 
     // Perform the action.
     match action {
@@ -59,13 +78,69 @@ fn main() -> anyhow::Result<()> {
             description,
             estimated_time,
             at,
-        } => interface::add_task(database, description, estimated_time, at),
-        List => interface::list(database),
-        Pauses => interface::pauses(database),
-        Start => interface::start(database),
-        Stop => interface::stop(database),
-        Next => interface::next(database),
-        Rm { position } => interface::remove_task(database, position),
+            fixed,
+            auto,
+        } => {
+            let estimated_time = if auto { None } else { estimated_time };
+            interface::add_task(&mut journal, description, estimated_time, at, fixed)
+        }
+        Suggest { description } => interface::suggest(&journal, description),
+        List {
+            forecast,
+            all_contexts,
+        } => interface::list(&mut journal, forecast, all_contexts),
+        Forecast => interface::forecast(&journal),
+        Plan { until } => interface::plan(&mut journal, until),
+        Pauses => interface::pauses(&journal),
+        Export(ExportCommand::Ics { day, output }) => {
+            interface::export_ics(&journal, day, output)
+        }
+        Export(ExportCommand::Markdown { day, output }) => {
+            interface::export_markdown(&journal, day, output)
+        }
+        Import {
+            file,
+            format_file,
+            format,
+        } => interface::import(&mut journal, file, format_file, format),
+        Convert {
+            from,
+            to,
+            to_backend,
+        } => interface::convert(&journal, &from, backend, &to, to_backend),
+        Serve { listen } => server::serve(&listen, &open_journal),
+        Pomodoro { work, short, long } => interface::pomodoro(&open_journal, work, short, long),
+        Merge { file } => interface::merge(&mut journal, &file),
+        Standup { since, markdown } => interface::standup(&journal, since, markdown),
+        Timesheet {
+            from,
+            to,
+            group,
+            round,
+            increment,
+            output,
+        } => interface::timesheet(
+            &journal, from, to, group, round, increment, output,
+        ),
+        Break(BreakCommand::Add { start, duration }) => {
+            interface::add_break(&mut journal, start, duration)
+        }
+        Break(BreakCommand::Rm { id }) => interface::remove_break(&mut journal, id),
+        Break(BreakCommand::List) => interface::breaks_list(&journal),
+        Context(ContextCommand::Use { name }) => interface::use_context(&mut journal, name),
+        Context(ContextCommand::List) => interface::contexts_list(&journal),
+        Start => interface::start(&mut journal),
+        Stop => interface::stop(&mut journal),
+        Next => interface::next(&mut journal),
+        Done { position, duration } => interface::done(&mut journal, position, duration),
+        Reopen { position } => interface::reopen(&mut journal, position),
+        Show { position } => interface::show(&journal, position),
+        Note { position, text } => interface::note(&mut journal, position, text),
+        Rm { position } => interface::remove_task(&mut journal, position),
+        Mv { from, to } => interface::move_task(&mut journal, from, to),
     }?;
+    if changes_journal {
+        webhooks::notify(&mut journal, state);
+    }
     Ok(())
 }
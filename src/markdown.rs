@@ -0,0 +1,108 @@
+// Markdown rendering of the journal, meant to be pasted in notes: the
+// tasks of a day as a checklist. Durations are rounded to the minute,
+// so that rendering the same day twice gives the same text.
+
+use crate::clock::Timezone;
+use crate::journal::Day;
+use crate::model::{Task, TaskExtra, TaskState};
+use chrono::Duration;
+
+/// Renders the log of a day: its tasks, with the time worked on each
+/// of them, their notes and commits, and the pauses, at their times in
+/// the given timezone.
+pub fn write_day_log(day: &Day, timezone: Timezone) -> String {
+    let mut log = format!("## {}\n\n", day.day.format("%Y-%m-%d"));
+
+    if day.tasks.is_empty() {
+        log.push_str("No tasks.\n");
+    }
+    for day_task in &day.tasks {
+        log.push_str(&checklist_item(&day_task.task, day_task.ellapsed));
+        if let Some(note) = &day_task.task.note {
+            for line in note.lines() {
+                log.push_str(&format!("    - {}\n", line));
+            }
+        }
+        if let Some(git) = &day_task.task.git {
+            log.push_str(&format!("    - In {}", git.repository));
+            if let Some(branch) = &git.branch {
+                log.push_str(&format!(" on {}", branch));
+            }
+            log.push('\n');
+            for commit in &git.commits {
+                log.push_str(&format!("    - Commit {}\n", commit));
+            }
+        }
+    }
+
+    if !day.pauses.is_empty() {
+        log.push_str("\nPauses:\n\n");
+        for (start, end) in &day.pauses {
+            match end {
+                Some(end) => log.push_str(&format!(
+                    "- {} - {} ({})\n",
+                    timezone.localize(*start).format("%H:%M"),
+                    timezone.localize(*end).format("%H:%M"),
+                    format_minutes(*end - *start)
+                )),
+                None => log.push_str(&format!(
+                    "- {} - now\n",
+                    timezone.localize(*start).format("%H:%M")
+                )),
+            }
+        }
+    }
+
+    let worked = day
+        .tasks
+        .iter()
+        .fold(Duration::zero(), |worked, day_task| worked + day_task.ellapsed);
+    let estimated = day.tasks.iter().fold(Duration::zero(), |estimated, day_task| {
+        estimated + day_task.task.estimated_duration
+    });
+    log.push_str(&format!(
+        "\nWorked {} of {} estimated.\n",
+        format_minutes(worked),
+        format_minutes(estimated)
+    ));
+    log
+}
+
+/// A task as a checklist item, like "- \[x\] Write report (took 35m,
+/// estimated 30m)".
+pub fn checklist_item(task: &Task, ellapsed: Duration) -> String {
+    let appointment = match task.fixed_at {
+        Some(fixed_at) => format!(" at {}", fixed_at.format("%H:%M")),
+        None => String::new(),
+    };
+    let times = match task.state() {
+        TaskState::Done => format!(
+            "took {}, estimated {}",
+            format_minutes(ellapsed),
+            format_minutes(task.estimated_duration)
+        ),
+        TaskState::Active => format!(
+            "in progress, worked {}, estimated {}",
+            format_minutes(ellapsed),
+            format_minutes(task.estimated_duration)
+        ),
+        TaskState::Pending => format!("estimated {}", format_minutes(task.estimated_duration)),
+    };
+    format!(
+        "- [{}] {}{} ({})\n",
+        if task.is_done() { "x" } else { " " },
+        task.description,
+        appointment,
+        times
+    )
+}
+
+/// Formats a duration as hours and minutes, like "1h 5m".
+fn format_minutes(duration: Duration) -> String {
+    let minutes = (duration.num_seconds() + 30) / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{}m", minutes),
+        (hours, 0) => format!("{}h", hours),
+        (hours, minutes) => format!("{}h {}m", hours, minutes),
+    }
+}
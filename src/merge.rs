@@ -0,0 +1,223 @@
+// Merging the journal of another machine into this one. Tasks are
+// matched by their UUID: the ones missing here are added, after the
+// task that preceded them there, and the ones changed on both sides are
+// reported as conflicts and left as they are here. Pomodoros only add
+// up, so the larger count of a task is kept. The work events,
+// end of work and breaks of a day are taken when they only add to the
+// ones here.
+
+use crate::model::{Task, TaskExtra};
+use crate::storage::{NewTask, Storage, DEFAULT_CONTEXT};
+use anyhow::{Context, Result};
+use chrono::{NaiveDate, NaiveTime};
+use std::fmt;
+
+/// What merging a journal did.
+#[derive(Debug, Default)]
+pub struct Merge {
+    /// The tasks added, with their position in the other journal.
+    pub added: Vec<(Task, u32)>,
+    /// The number of tasks that were already here, unchanged but for
+    /// their pomodoros.
+    pub unchanged: usize,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// A difference between the journals that merging left unresolved.
+#[derive(Debug)]
+pub enum Conflict {
+    /// The task was changed in both journals.
+    Task { ours: Box<Task>, theirs: Box<Task> },
+    /// The task is active in the other journal, but another task is
+    /// active here. It was added as not started.
+    Active { theirs: Box<Task> },
+    /// Work was started and stopped at different times in each journal.
+    Work { context: String, day: NaiveDate },
+    EndOfWork {
+        day: NaiveDate,
+        ours: NaiveTime,
+        theirs: NaiveTime,
+    },
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Conflict::Task { ours, theirs } => write!(
+                f,
+                "{}: task {} ({}) differs from task {} ({}) of the other journal.",
+                in_context(&ours.context, &ours.day),
+                ours.position,
+                ours.description,
+                theirs.position,
+                theirs.description
+            ),
+            Conflict::Active { theirs } => write!(
+                f,
+                "{}: task {} ({}) is active in the other journal, but another task is active here. It was added as not started.",
+                in_context(&theirs.context, &theirs.day),
+                theirs.position,
+                theirs.description
+            ),
+            Conflict::Work { context, day } => write!(
+                f,
+                "{}: work was started and stopped at different times in the other journal.",
+                in_context(context, day)
+            ),
+            Conflict::EndOfWork { day, ours, theirs } => write!(
+                f,
+                "{}: the end of work is {} here, and {} in the other journal.",
+                day,
+                ours.format("%H:%M"),
+                theirs.format("%H:%M")
+            ),
+        }
+    }
+}
+
+/// Merges the journal 'from' into 'into'.
+pub fn merge(into: &mut dyn Storage, from: &dyn Storage) -> Result<Merge> {
+    let mut merge = Merge::default();
+    let contexts = from.contexts()?;
+    for day in from.days()? {
+        for context in &contexts {
+            merge_tasks(into, from, context, day, &mut merge)?;
+            merge_work(into, from, context, day, &mut merge)?;
+        }
+
+        if let Some(theirs) = from.end_of_work(day)? {
+            match into.end_of_work(day)? {
+                None => into.set_end_of_work(day, theirs)?,
+                Some(ours) if ours != theirs => merge
+                    .conflicts
+                    .push(Conflict::EndOfWork { day, ours, theirs }),
+                Some(_) => {}
+            }
+        }
+
+        let breaks = into.planned_breaks(day)?;
+        for theirs in from.planned_breaks(day)? {
+            if !breaks
+                .iter()
+                .any(|ours| ours.start == theirs.start && ours.duration == theirs.duration)
+            {
+                into.add_planned_break(day, theirs.start, theirs.duration)?;
+            }
+        }
+    }
+    Ok(merge)
+}
+
+/// Adds the tasks of the day missing here, each after the task that
+/// preceded it in the other journal.
+fn merge_tasks(
+    into: &mut dyn Storage,
+    from: &dyn Storage,
+    context: &str,
+    day: NaiveDate,
+    merge: &mut Merge,
+) -> Result<()> {
+    let mut previous_position = 0;
+    for theirs in from.tasks(context, day)? {
+        let tasks = into.tasks(context, day)?;
+        if let Some(ours) = tasks.iter().find(|ours| ours.uuid == theirs.uuid) {
+            previous_position = ours.position;
+            if is_same(ours, &theirs) {
+                if theirs.pomodoros > ours.pomodoros {
+                    into.set_pomodoros(context, day, ours.position, theirs.pomodoros)?;
+                }
+                merge.unchanged += 1;
+            } else {
+                merge.conflicts.push(Conflict::Task {
+                    ours: Box::new(ours.clone()),
+                    theirs: Box::new(theirs),
+                });
+            }
+            continue;
+        }
+
+        let position = previous_position + 1;
+        let task = NewTask {
+            uuid: theirs.uuid.clone(),
+            description: theirs.description.clone(),
+            estimated_duration: theirs.estimated_duration,
+            fixed_at: theirs.fixed_at,
+            source_uid: theirs.source_uid.clone(),
+            created_at: theirs.created_at,
+        };
+        into.insert_task(context, day, position, &task)?;
+        if theirs.is_active() && tasks.iter().any(|ours| ours.is_active()) {
+            merge.conflicts.push(Conflict::Active {
+                theirs: Box::new(theirs.clone()),
+            });
+        } else {
+            if theirs.started_at.is_some() {
+                into.set_started(context, day, position, theirs.started_at)?;
+            }
+            if theirs.finished_at.is_some() {
+                into.set_finished(context, day, position, theirs.finished_at)?;
+            }
+        }
+        if let Some(note) = &theirs.note {
+            into.add_note(context, day, position, note)?;
+        }
+        if theirs.git.is_some() {
+            into.set_git(context, day, position, theirs.git.as_ref())?;
+        }
+        if theirs.pomodoros > 0 {
+            into.set_pomodoros(context, day, position, theirs.pomodoros)?;
+        }
+
+        previous_position = position;
+        let added = into
+            .tasks(context, day)?
+            .into_iter()
+            .find(|task| task.position == position)
+            .context("Failed to add task.")?;
+        merge.added.push((added, theirs.position));
+    }
+    Ok(())
+}
+
+/// Takes the work events of the day of the other journal if they
+/// continue the ones here.
+fn merge_work(
+    into: &mut dyn Storage,
+    from: &dyn Storage,
+    context: &str,
+    day: NaiveDate,
+    merge: &mut Merge,
+) -> Result<()> {
+    let ours = into.work_events(context, day)?;
+    let theirs = from.work_events(context, day)?;
+    if theirs.starts_with(&ours) {
+        for &at in &theirs[ours.len()..] {
+            into.add_work_event(context, day, at)?;
+        }
+    } else if !ours.starts_with(&theirs) {
+        merge.conflicts.push(Conflict::Work {
+            context: context.to_string(),
+            day,
+        });
+    }
+    Ok(())
+}
+
+/// A day, followed by its context if not the default one.
+fn in_context(context: &str, day: &dyn fmt::Display) -> String {
+    if context == DEFAULT_CONTEXT {
+        day.to_string()
+    } else {
+        format!("{} @{}", day, context)
+    }
+}
+
+fn is_same(ours: &Task, theirs: &Task) -> bool {
+    ours.description == theirs.description
+        && ours.estimated_duration == theirs.estimated_duration
+        && ours.fixed_at == theirs.fixed_at
+        && ours.started_at == theirs.started_at
+        && ours.finished_at == theirs.finished_at
+        && ours.note == theirs.note
+        && ours.git == theirs.git
+}
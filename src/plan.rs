@@ -0,0 +1,243 @@
+// Projection of the daily plan: when the unfinished tasks are expected
+// to start and end.
+//
+// Appointments (tasks with a fixed start time) are anchored at their
+// time. The active task goes first, and the other tasks are worked on
+// one after the other, by position, around the appointments and the
+// planned breaks.
+
+use crate::clock::Timezone;
+use crate::model;
+use crate::model::{Pauses, Task, TaskExtra, TaskState};
+use chrono::{DateTime, Duration, Local, NaiveDate};
+
+/// The expected start and end of an unfinished task.
+#[derive(Debug)]
+pub struct Projection {
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    /// Positions of the appointments the task runs into.
+    pub collisions: Vec<u32>,
+    /// Whether the task is an appointment whose time has passed but has
+    /// not been started.
+    pub late: bool,
+}
+
+impl Projection {
+    /// Whether the plan will not work out as expected for this task.
+    pub fn has_conflict(&self) -> bool {
+        self.late || !self.collisions.is_empty()
+    }
+}
+
+/// How the unfinished work fits before the end of the work day.
+#[derive(Debug)]
+pub struct Capacity {
+    pub end_of_work: DateTime<Local>,
+    /// Time left until the end of work.
+    pub available: Duration,
+    /// Estimated time needed to finish all the unfinished tasks.
+    pub planned: Duration,
+    /// Positions of the tasks expected to end after the end of work.
+    pub overrunning: Vec<u32>,
+}
+
+/// The breaks planned for the day, as (start, end) ranges.
+pub type Breaks = Vec<(DateTime<Local>, DateTime<Local>)>;
+
+/// A span of time reserved for something other than the flexible tasks:
+/// an appointment, identified by its position, or a break.
+struct Blocked {
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+    appointment: Option<u32>,
+}
+
+/// Returns the start of an appointment, or None if the task is not one.
+pub fn appointment_start(task: &Task, timezone: Timezone) -> Option<DateTime<Local>> {
+    let day = NaiveDate::parse_from_str(&task.day, "%Y-%m-%d").ok()?;
+    timezone.at(day, task.fixed_at?)
+}
+
+/// Compares the unfinished work, and its projection, with the time
+/// left from 'now' until the end of work, breaks excluded.
+pub fn capacity(
+    tasks: &[Task],
+    pauses: &Pauses,
+    breaks: &[(DateTime<Local>, DateTime<Local>)],
+    projections: &[Option<Projection>],
+    end_of_work: DateTime<Local>,
+    now: DateTime<Local>,
+) -> Capacity {
+    let mut available = std::cmp::max(Duration::seconds(0), end_of_work - now);
+    for (start, end) in merge_breaks(breaks) {
+        let overlap = std::cmp::min(end, end_of_work) - std::cmp::max(start, now);
+        available = available - std::cmp::max(Duration::seconds(0), overlap);
+    }
+
+    let mut planned = Duration::seconds(0);
+    for task in tasks {
+        planned = planned + model::remaining_time(task, pauses, now);
+    }
+
+    let overrunning = tasks
+        .iter()
+        .zip(projections.iter())
+        .filter(|(_, projection)| matches!(projection, Some(projection) if projection.end > end_of_work))
+        .map(|(task, _)| task.position)
+        .collect();
+
+    Capacity {
+        end_of_work,
+        available,
+        planned,
+        overrunning,
+    }
+}
+
+/// Returns the breaks sorted by start, with the overlapping ones merged,
+/// so that the time they share is counted once.
+fn merge_breaks(breaks: &[(DateTime<Local>, DateTime<Local>)]) -> Breaks {
+    let mut sorted = breaks.to_vec();
+    sorted.sort();
+    let mut merged: Breaks = Vec::new();
+    for (start, end) in sorted {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = std::cmp::max(last.1, end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Returns the projection of every task from 'now', in the same
+/// order. Done tasks have none.
+pub fn project(
+    tasks: &[Task],
+    pauses: &Pauses,
+    breaks: &[(DateTime<Local>, DateTime<Local>)],
+    now: DateTime<Local>,
+    timezone: Timezone,
+) -> Vec<Option<Projection>> {
+    let remaining_times: Vec<Duration> = tasks
+        .iter()
+        .map(|task| model::remaining_time(task, pauses, now))
+        .collect();
+    project_with(tasks, &remaining_times, breaks, now, timezone)
+}
+
+/// Returns the projection of every task, in the same order, supposing
+/// each task still needs the given remaining time from 'now'.
+pub fn project_with(
+    tasks: &[Task],
+    remaining_times: &[Duration],
+    breaks: &[(DateTime<Local>, DateTime<Local>)],
+    now: DateTime<Local>,
+    timezone: Timezone,
+) -> Vec<Option<Projection>> {
+    let mut projections: Vec<Option<Projection>> = tasks.iter().map(|_| None).collect();
+
+    let mut blocked: Vec<Blocked> = breaks
+        .iter()
+        .filter(|(_, end)| *end > now)
+        .map(|(start, end)| Blocked {
+            start: std::cmp::max(*start, now),
+            end: *end,
+            appointment: None,
+        })
+        .collect();
+
+    // Appointments not started yet are anchored to their time.
+    for (index, task) in tasks.iter().enumerate() {
+        if let (TaskState::Pending, Some(start)) = (task.state(), appointment_start(task, timezone))
+        {
+            let end = start + remaining_times[index];
+            projections[index] = Some(Projection {
+                start,
+                end,
+                collisions: Vec::new(),
+                late: start < now,
+            });
+            if end > now {
+                blocked.push(Blocked {
+                    start: std::cmp::max(start, now),
+                    end,
+                    appointment: Some(task.position),
+                });
+            }
+        }
+    }
+    blocked.sort_by_key(|span| span.start);
+
+    let mut cursor = now;
+
+    // The active task goes first. An appointment in progress can not be
+    // interrupted by the following ones, it may only overrun them.
+    for (index, task) in tasks.iter().enumerate() {
+        if !task.is_active() {
+            continue;
+        }
+        let projection = if task.fixed_at.is_some() {
+            let end = now + remaining_times[index];
+            Projection {
+                start: now,
+                end,
+                collisions: blocked
+                    .iter()
+                    .filter(|span| span.start < end)
+                    .filter_map(|span| span.appointment)
+                    .collect(),
+                late: false,
+            }
+        } else {
+            flow(now, remaining_times[index], &blocked)
+        };
+        cursor = projection.end;
+        projections[index] = Some(projection);
+    }
+
+    // Then the pending flexible tasks, by position.
+    for (index, task) in tasks.iter().enumerate() {
+        if matches!(task.state(), TaskState::Pending) && task.fixed_at.is_none() {
+            let projection = flow(cursor, remaining_times[index], &blocked);
+            cursor = projection.end;
+            projections[index] = Some(projection);
+        }
+    }
+
+    projections
+}
+
+/// Projects 'duration' of work from 'from', skipping over the blocked
+/// spans. A task that would start during a blocked span starts after
+/// it, and one interrupted by an appointment collides with it.
+fn flow(from: DateTime<Local>, duration: Duration, blocked: &[Blocked]) -> Projection {
+    let mut time = from;
+    let mut left = duration;
+    let mut start = None;
+    let mut collisions = Vec::new();
+
+    for span in blocked {
+        if span.end <= time {
+            continue;
+        }
+        if span.start <= time {
+            time = span.end;
+            continue;
+        }
+        start.get_or_insert(time);
+        if time + left <= span.start {
+            break;
+        }
+        left = left - (span.start - time);
+        collisions.extend(span.appointment);
+        time = span.end;
+    }
+
+    Projection {
+        start: start.unwrap_or(time),
+        end: time + left,
+        collisions,
+        late: false,
+    }
+}
@@ -0,0 +1,124 @@
+// Pomodoros: work on the active task in intervals separated by short
+// breaks, with a long break after every fourth one. Work is stopped
+// during the breaks, so that they are recorded as pauses, and the
+// intervals worked all along on the same task are counted on it.
+//
+// The journal is opened again after each interval and break, so that
+// the commands run meanwhile are seen, and the time passes on the clock
+// of the journal.
+
+use crate::hooks;
+use crate::hooks::Event;
+use crate::model::{Task, WorkState};
+use crate::webhooks;
+use crate::{Error, Journal};
+use anyhow::Result;
+use chrono::{DateTime, Duration, Local};
+
+/// The number of intervals between long breaks.
+pub const INTERVALS_BEFORE_LONG_BREAK: u32 = 4;
+
+/// The lengths of the work intervals and of the breaks.
+#[derive(Debug, Clone, Copy)]
+pub struct Schedule {
+    pub work: Duration,
+    pub short_break: Duration,
+    pub long_break: Duration,
+}
+
+/// What happens while working in pomodoros.
+#[derive(Debug)]
+pub enum Step {
+    /// An interval of work on the task begins, ending at the given time.
+    Working(Task, DateTime<Local>),
+    /// An interval was completed, counted on the task, and a break
+    /// begins, ending at the given time.
+    Break(Task, DateTime<Local>),
+    /// Another task was started during the interval: a new one begins.
+    TaskChanged,
+    /// Work was stopped and started again during the interval: a new one
+    /// begins.
+    WorkRestarted,
+    /// Work was stopped during the interval: the pomodoros end.
+    WorkStopped,
+    /// Work was started during the break: the pomodoros end.
+    StartedDuringBreak,
+}
+
+/// Works in pomodoros on the active task, starting work if stopped,
+/// until work is stopped during an interval or started during a break.
+/// Each step is told to 'on_step' as it begins.
+pub fn run(
+    open_journal: &dyn Fn() -> Result<Journal>,
+    schedule: Schedule,
+    on_step: &mut dyn FnMut(&Step),
+) -> Result<()> {
+    let mut journal = open_journal()?;
+    if matches!(journal.work_state()?, WorkState::Stopped) {
+        switch_work(&mut journal)?;
+    }
+
+    let mut completed = 0;
+    loop {
+        let task = journal.active_task()?.ok_or(Error::NoTasks)?;
+        let work_events = journal.work_events(journal.today()?)?.len();
+        let uuid = task.uuid.clone();
+        on_step(&Step::Working(task, journal.now() + schedule.work));
+        journal.sleep(schedule.work);
+
+        journal = open_journal()?;
+        if matches!(journal.work_state()?, WorkState::Stopped) {
+            on_step(&Step::WorkStopped);
+            return Ok(());
+        }
+        let active = journal.active_task()?.ok_or(Error::NoTasks)?;
+        if active.uuid != uuid {
+            on_step(&Step::TaskChanged);
+            continue;
+        }
+        if journal.work_events(journal.today()?)?.len() != work_events {
+            on_step(&Step::WorkRestarted);
+            continue;
+        }
+        let task = journal.add_pomodoro(active.position)?;
+        completed += 1;
+        let pause = if completed % INTERVALS_BEFORE_LONG_BREAK == 0 {
+            schedule.long_break
+        } else {
+            schedule.short_break
+        };
+        switch_work(&mut journal)?;
+        let break_events = journal.work_events(journal.today()?)?.len();
+        on_step(&Step::Break(task, journal.now() + pause));
+        journal.sleep(pause);
+
+        journal = open_journal()?;
+        if journal.work_events(journal.today()?)?.len() != break_events {
+            on_step(&Step::StartedDuringBreak);
+            return Ok(());
+        }
+        switch_work(&mut journal)?;
+    }
+}
+
+/// Starts or stops work like the 'start' and 'stop' commands, hooks and
+/// webhooks included.
+fn switch_work(journal: &mut Journal) -> Result<()> {
+    let state = webhooks::state(journal)?;
+    match journal.work_state()? {
+        WorkState::Running => {
+            journal.stop()?;
+            let task = journal.active_task()?;
+            hooks::run(journal, Event::Stop, task.as_ref(), None);
+        }
+        WorkState::Stopped => {
+            let task = match journal.start()? {
+                Some(task) => Some(task),
+                None => journal.active_task()?,
+            };
+            hooks::run(journal, Event::Start, task.as_ref(), None);
+        }
+    }
+    webhooks::notify(journal, state);
+    Ok(())
+}
@@ -0,0 +1,447 @@
+// A local HTTP server exposing the journal as a JSON API, for
+// dashboards and editor plugins. The journal is opened again for each
+// request, so that changes made meanwhile by other commands are seen.
+//
+//     GET    /tasks                  today's tasks, with their times
+//     POST   /tasks                  {"description", "estimate"?, "at"?, "fixed"?}
+//     DELETE /tasks/:position
+//     POST   /tasks/:position/move   {"to"}
+//     POST   /start, /stop, /next
+//     GET    /history?from=&to=      finished tasks, by day (YYYY-MM-DD)
+//
+// Durations are in seconds in responses, and strings like "30m" in
+// requests. Operations that are not allowed answer with an HTTP error
+// and {"error": message}. The changes run the hooks and notify the
+// webhooks, as on the command line.
+//
+// Web pages must not be able to use the API: it only listens on
+// loopback addresses, requests must name the listen address or
+// localhost in their Host header, the ones changing the journal must
+// not come from another site according to their Origin header, and
+// requests with a body must send it as application/json, which
+// browsers do not allow across sites without asking first.
+
+use crate::config::{parse_day, parse_duration, parse_time};
+use crate::hooks;
+use crate::hooks::Event;
+use crate::journal::DayTask;
+use crate::json::{optional_time, task_json};
+use crate::model::WorkState;
+use crate::webhooks;
+use crate::{Error, Journal};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::net::{SocketAddr, ToSocketAddrs};
+use tiny_http::{Header, Response, Server};
+
+pub use tiny_http::Method;
+
+/// A request to the API, read in full from the connection.
+#[derive(Debug)]
+pub struct Request {
+    pub method: Method,
+    /// The path, and the query if any.
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// An error answered to a request.
+struct HttpError {
+    status: u16,
+    message: String,
+}
+
+impl HttpError {
+    fn new(status: u16, message: impl ToString) -> HttpError {
+        HttpError {
+            status,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<Error> for HttpError {
+    fn from(error: Error) -> HttpError {
+        let status = match error {
+            Error::NoSuchTask(_) | Error::NoSuchBreak(_) => 404,
+            Error::AlreadyDone(_)
+            | Error::NotDone(_)
+            | Error::AlreadyStarted(_)
+            | Error::MoveStarted(_)
+            | Error::DurationOfStartedTask(_)
+            | Error::AnotherTaskActive(_)
+            | Error::AlreadyWorking
+            | Error::NotWorking
+            | Error::NoTasks
+            | Error::WorkStopped
+            | Error::CloseBeforeLastActivity => 409,
+            Error::NoSimilarTasks | Error::NotEnoughHistory { .. } => 422,
+            Error::InvalidContext(_) => 400,
+            Error::Storage(_) => 500,
+        };
+        HttpError::new(status, error)
+    }
+}
+
+impl From<anyhow::Error> for HttpError {
+    fn from(error: anyhow::Error) -> HttpError {
+        HttpError::new(500, error)
+    }
+}
+
+#[derive(Deserialize)]
+struct NewTask {
+    description: String,
+    estimate: Option<String>,
+    at: Option<u32>,
+    fixed: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Move {
+    to: u32,
+}
+
+/// Serves the API on the given address until interrupted.
+pub fn serve(listen: &str, open_journal: &dyn Fn() -> Result<Journal>) -> Result<()> {
+    check_loopback(listen)?;
+    let server = Server::http(listen)
+        .map_err(|error| anyhow!("Failed to listen on {}: {}", listen, error))?;
+    println!("Listening on http://{}", listen);
+
+    for mut request in server.incoming_requests() {
+        let (status, body) = match read_request(&mut request) {
+            Ok(api_request) => handle(&api_request, listen, open_journal),
+            Err(error) => (400, json!({ "error": format!("{:#}", error) })),
+        };
+        let response = Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(
+                Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+                    .expect("Invalid header."),
+            );
+        if let Err(error) = request.respond(response) {
+            eprintln!("Failed to respond: {}", error);
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the listen address is only reachable from this machine,
+/// as the API has no authentication.
+fn check_loopback(listen: &str) -> Result<()> {
+    let addresses: Vec<SocketAddr> = listen
+        .to_socket_addrs()
+        .map_err(|error| anyhow!("Invalid listen address {}: {}", listen, error))?
+        .collect();
+    if addresses.is_empty() || !addresses.iter().all(|address| address.ip().is_loopback()) {
+        bail!(
+            "Cannot listen on {}: only loopback addresses are allowed, like 127.0.0.1:8080.",
+            listen
+        );
+    }
+    Ok(())
+}
+
+/// Reads a request in full, to handle it apart from the connection.
+fn read_request(request: &mut tiny_http::Request) -> Result<Request> {
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .read_to_end(&mut body)
+        .context("Failed to read the request.")?;
+    Ok(Request {
+        method: request.method().clone(),
+        url: request.url().to_string(),
+        headers: request
+            .headers()
+            .iter()
+            .map(|header| (header.field.to_string(), header.value.to_string()))
+            .collect(),
+        body,
+    })
+}
+
+/// Answers a request to the API served on the 'listen' address: the
+/// status, and the JSON body of the response.
+pub fn handle(
+    request: &Request,
+    listen: &str,
+    open_journal: &dyn Fn() -> Result<Journal>,
+) -> (u16, Value) {
+    match handle_request(request, listen, open_journal) {
+        Ok((status, body)) => (status, body),
+        Err(error) => (error.status, json!({ "error": error.message })),
+    }
+}
+
+fn handle_request(
+    request: &Request,
+    listen: &str,
+    open_journal: &dyn Fn() -> Result<Journal>,
+) -> Result<(u16, Value), HttpError> {
+    let (path, query) = match request.url.split_once('?') {
+        Some((path, query)) => (path, query),
+        None => (request.url.as_str(), ""),
+    };
+    let segments: Vec<&str> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    let method = &request.method;
+    check_host(request, listen)?;
+    let route = route(method, &segments)?;
+    if *method != Method::Get {
+        check_origin(request, listen)?;
+    }
+    if !request.body.is_empty() && !is_json(request) {
+        return Err(HttpError::new(
+            415,
+            "Expected a request with Content-Type application/json.",
+        ));
+    }
+
+    let mut journal = open_journal()?;
+    let state = if *method == Method::Get {
+        None
+    } else {
+        webhooks::state(&journal)?
+    };
+    let response = match route {
+        Route::Tasks => Ok((200, day_json(&journal)?)),
+        Route::AddTask => {
+            let new_task: NewTask = read_json(request)?;
+            let estimate = match &new_task.estimate {
+                Some(estimate) => {
+                    parse_duration(estimate).map_err(|error| HttpError::new(400, error))?
+                }
+                None => journal.suggest(&new_task.description)?.estimate,
+            };
+            let fixed_at = match &new_task.fixed {
+                Some(fixed) => Some(parse_time(fixed).map_err(|error| HttpError::new(400, error))?),
+                None => None,
+            };
+            let task = journal.add(&new_task.description, estimate, new_task.at, fixed_at)?;
+            hooks::run(&journal, Event::Add, Some(&task), None);
+            Ok((201, task_json(&task)))
+        }
+        Route::RemoveTask(position) => {
+            let task = journal.remove(parse_position(position)?)?;
+            hooks::run(&journal, Event::Remove, Some(&task), None);
+            Ok((200, task_json(&task)))
+        }
+        Route::MoveTask(position) => {
+            let from = parse_position(position)?;
+            let Move { to } = read_json(request)?;
+            let task = journal.move_task(from, to)?;
+            Ok((200, task_json(&task)))
+        }
+        Route::Start => {
+            let started = journal.start()?;
+            let task = match &started {
+                Some(task) => Some(task.clone()),
+                None => journal.active_task()?,
+            };
+            hooks::run(&journal, Event::Start, task.as_ref(), None);
+            Ok((200, json!({ "started": started.as_ref().map(task_json) })))
+        }
+        Route::Stop => {
+            journal.stop()?;
+            let task = journal.active_task()?;
+            hooks::run(&journal, Event::Stop, task.as_ref(), None);
+            Ok((200, json!({ "working": false })))
+        }
+        Route::Next => {
+            let next = journal.next()?;
+            hooks::run(
+                &journal,
+                Event::Next,
+                next.started.as_ref(),
+                next.finished.as_ref(),
+            );
+            Ok((
+                200,
+                json!({
+                    "finished": next.finished.as_ref().map(task_json),
+                    "started": next.started.as_ref().map(task_json),
+                }),
+            ))
+        }
+        Route::History => {
+            let mut from = None;
+            let mut to = None;
+            for (key, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+                let day = parse_day(value).map_err(|error| HttpError::new(400, error))?;
+                match key {
+                    "from" => from = Some(day),
+                    "to" => to = Some(day),
+                    _ => {}
+                }
+            }
+            let tasks: Vec<Value> = journal
+                .finished_tasks(from, to)?
+                .iter()
+                .map(|(task, ellapsed)| {
+                    let mut task_json = task_json(task);
+                    task_json["day"] = json!(task.day);
+                    task_json["ellapsed"] = json!(ellapsed.num_seconds());
+                    task_json
+                })
+                .collect();
+            Ok((200, json!({ "tasks": tasks })))
+        }
+    };
+    if response.is_ok() {
+        webhooks::notify(&mut journal, state);
+    }
+    response
+}
+
+/// The operations of the API.
+enum Route<'a> {
+    Tasks,
+    AddTask,
+    RemoveTask(&'a str),
+    MoveTask(&'a str),
+    Start,
+    Stop,
+    Next,
+    History,
+}
+
+/// The operation requested, or an error if the path is unknown or does
+/// not accept the method.
+fn route<'a>(method: &Method, segments: &[&'a str]) -> Result<Route<'a>, HttpError> {
+    match (method, segments) {
+        (Method::Get, ["tasks"]) => Ok(Route::Tasks),
+        (Method::Post, ["tasks"]) => Ok(Route::AddTask),
+        (Method::Delete, ["tasks", position]) => Ok(Route::RemoveTask(position)),
+        (Method::Post, ["tasks", position, "move"]) => Ok(Route::MoveTask(position)),
+        (Method::Post, ["start"]) => Ok(Route::Start),
+        (Method::Post, ["stop"]) => Ok(Route::Stop),
+        (Method::Post, ["next"]) => Ok(Route::Next),
+        (Method::Get, ["history"]) => Ok(Route::History),
+        (_, ["tasks"])
+        | (_, ["tasks", _])
+        | (_, ["tasks", _, "move"])
+        | (_, ["start"])
+        | (_, ["stop"])
+        | (_, ["next"])
+        | (_, ["history"]) => Err(HttpError::new(405, "Method not allowed.")),
+        _ => Err(HttpError::new(404, "Not found.")),
+    }
+}
+
+/// Today's tasks, with the time worked on them and their expected
+/// start and end.
+fn day_json(journal: &Journal) -> Result<Value, HttpError> {
+    let day = journal.day(journal.today()?)?;
+    let tasks: Vec<Value> = day.tasks.iter().map(day_task_json).collect();
+    let pauses: Vec<Value> = day
+        .pauses
+        .iter()
+        .map(|(start, end)| json!({ "start": start.to_rfc3339(), "end": optional_time(*end) }))
+        .collect();
+    let capacity = day.capacity.as_ref().map(|capacity| {
+        json!({
+            "end_of_work": capacity.end_of_work.to_rfc3339(),
+            "available": capacity.available.num_seconds(),
+            "planned": capacity.planned.num_seconds(),
+            "overrunning": capacity.overrunning,
+        })
+    });
+
+    Ok(json!({
+        "day": day.day.format("%Y-%m-%d").to_string(),
+        "context": journal.context(),
+        "working": matches!(journal.work_state()?, WorkState::Running),
+        "tasks": tasks,
+        "pauses": pauses,
+        "capacity": capacity,
+    }))
+}
+
+fn day_task_json(day_task: &DayTask) -> Value {
+    let mut task = task_json(&day_task.task);
+    task["ellapsed"] = json!(day_task.ellapsed.num_seconds());
+    task["paused"] = json!(day_task.paused.num_seconds());
+    if let Some(projection) = &day_task.projection {
+        task["expected_start"] = json!(projection.start.to_rfc3339());
+        task["expected_end"] = json!(projection.end.to_rfc3339());
+        task["collisions"] = json!(projection.collisions);
+        task["late"] = json!(projection.late);
+    }
+    task
+}
+
+fn parse_position(position: &str) -> Result<u32, HttpError> {
+    position
+        .parse()
+        .map_err(|_| HttpError::new(400, format!("Invalid position {}.", position)))
+}
+
+/// The value of a header of the request, if it has it.
+fn header<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request
+        .headers
+        .iter()
+        .find(|(field, _)| field.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// The names the API can be reached at: the listen address, and
+/// localhost on the same port.
+fn allowed_hosts(listen: &str) -> [String; 4] {
+    let port = listen.rsplit(':').next().unwrap_or_default();
+    [
+        listen.to_string(),
+        format!("localhost:{}", port),
+        format!("127.0.0.1:{}", port),
+        format!("[::1]:{}", port),
+    ]
+}
+
+/// Checks that the request was sent to the listen address or to
+/// localhost, and not to another domain name resolving to it.
+fn check_host(request: &Request, listen: &str) -> Result<(), HttpError> {
+    let host = header(request, "Host").unwrap_or_default();
+    if allowed_hosts(listen)
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(host))
+    {
+        Ok(())
+    } else {
+        Err(HttpError::new(403, "Unexpected Host header."))
+    }
+}
+
+/// Checks that a request sent by a browser comes from a page of the API
+/// itself. Browsers name the page in the Origin header of the requests
+/// changing things, bodyless or not.
+fn check_origin(request: &Request, listen: &str) -> Result<(), HttpError> {
+    let origin = match header(request, "Origin") {
+        Some(origin) => origin,
+        None => return Ok(()),
+    };
+    if allowed_hosts(listen)
+        .iter()
+        .any(|name| format!("http://{}", name).eq_ignore_ascii_case(origin))
+    {
+        Ok(())
+    } else {
+        Err(HttpError::new(403, "Unexpected Origin header."))
+    }
+}
+
+fn is_json(request: &Request) -> bool {
+    header(request, "Content-Type")
+        .and_then(|content_type| content_type.split(';').next())
+        .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case("application/json"))
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(request: &Request) -> Result<T, HttpError> {
+    serde_json::from_slice(&request.body)
+        .map_err(|error| HttpError::new(400, format!("Invalid request: {}", error)))
+}
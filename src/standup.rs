@@ -0,0 +1,61 @@
+// Standup summaries: the tasks finished since the last working day, and
+// the ones planned for today, as text or as Markdown.
+
+use chrono::NaiveDate;
+
+/// The tasks to report in a standup.
+#[derive(Debug)]
+pub struct Standup {
+    /// What the finished tasks are reported for, like "Yesterday".
+    pub title: String,
+    /// The descriptions of the tasks finished since the first day
+    /// reported, until yesterday.
+    pub done: Vec<String>,
+    /// The descriptions of the unfinished tasks of today, with the time
+    /// of the appointments.
+    pub planned: Vec<String>,
+}
+
+/// The title of the finished tasks: the day given, or else the last
+/// working day before today, if any.
+pub fn title(
+    today: NaiveDate,
+    since: Option<NaiveDate>,
+    last_worked_day: Option<NaiveDate>,
+) -> String {
+    match (since, last_worked_day) {
+        (Some(since), _) => format!("Since {}", since.format("%A %Y-%m-%d")),
+        (None, Some(day)) if day.succ_opt() == Some(today) => "Yesterday".to_string(),
+        (None, Some(day)) => day.format("%A %Y-%m-%d").to_string(),
+        (None, None) => "Before today".to_string(),
+    }
+}
+
+/// Renders a standup, as a list under each title or as Markdown.
+pub fn write_standup(standup: &Standup, markdown: bool) -> String {
+    let indent = if markdown { "" } else { "  " };
+    let mut text = String::new();
+    for (index, (title, items)) in [
+        (standup.title.as_str(), &standup.done),
+        ("Today", &standup.planned),
+    ]
+    .iter()
+    .enumerate()
+    {
+        if index > 0 {
+            text.push('\n');
+        }
+        if markdown {
+            text.push_str(&format!("### {}\n\n", title));
+        } else {
+            text.push_str(&format!("{}:\n", title));
+        }
+        if items.is_empty() {
+            text.push_str(&format!("{}Nothing.\n", indent));
+        }
+        for item in items.iter() {
+            text.push_str(&format!("{}- {}\n", indent, item));
+        }
+    }
+    text
+}
@@ -0,0 +1,314 @@
+// A journal kept in memory, for tests and for tools embedding akiv. It
+// is also what the text journal is read into.
+
+use crate::model::{GitContext, PlannedBreak, Task};
+use crate::storage::{NewTask, QueuedEvent, Storage};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime};
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    tasks: Vec<Task>,
+    work: Vec<(String, NaiveDate, DateTime<Local>)>,
+    end_of_work: HashMap<NaiveDate, NaiveTime>,
+    breaks: Vec<(NaiveDate, PlannedBreak)>,
+    settings: HashMap<String, String>,
+    events: Vec<QueuedEvent>,
+    last_task_id: u32,
+    last_break_id: u32,
+    last_event_id: u32,
+}
+
+impl MemoryStorage {
+    fn task_mut(&mut self, context: &str, day: NaiveDate, position: u32) -> Result<&mut Task> {
+        let day = day.format("%Y-%m-%d").to_string();
+        match self.tasks.iter_mut().find(|task| {
+            task.context == context && task.day == day && task.position == position
+        }) {
+            Some(task) => Ok(task),
+            None => bail!("No task at position {} on {}.", position, day),
+        }
+    }
+
+    fn day_tasks_mut<'a>(
+        &'a mut self,
+        context: &'a str,
+        day: NaiveDate,
+    ) -> impl Iterator<Item = &'a mut Task> {
+        let day = day.format("%Y-%m-%d").to_string();
+        self.tasks
+            .iter_mut()
+            .filter(move |task| task.context == context && task.day == day)
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn contexts(&self) -> Result<Vec<String>> {
+        let mut contexts = BTreeSet::new();
+        contexts.extend(self.tasks.iter().map(|task| task.context.clone()));
+        contexts.extend(self.work.iter().map(|(context, _, _)| context.clone()));
+        Ok(contexts.into_iter().collect())
+    }
+
+    fn days(&self) -> Result<Vec<NaiveDate>> {
+        let mut days = BTreeSet::new();
+        for task in &self.tasks {
+            days.insert(NaiveDate::parse_from_str(&task.day, "%Y-%m-%d")?);
+        }
+        days.extend(self.work.iter().map(|(_, day, _)| *day));
+        days.extend(self.end_of_work.keys());
+        days.extend(self.breaks.iter().map(|(day, _)| *day));
+        Ok(days.into_iter().collect())
+    }
+
+    fn tasks(&self, context: &str, day: NaiveDate) -> Result<Vec<Task>> {
+        let day = day.format("%Y-%m-%d").to_string();
+        let mut tasks: Vec<Task> = self
+            .tasks
+            .iter()
+            .filter(|task| task.context == context && task.day == day)
+            .cloned()
+            .collect();
+        tasks.sort_by_key(|task| task.position);
+        Ok(tasks)
+    }
+
+    fn finished_tasks(
+        &self,
+        context: &str,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Result<Vec<Task>> {
+        let from = from.map(|from| from.format("%Y-%m-%d").to_string());
+        let to = to.map(|to| to.format("%Y-%m-%d").to_string());
+        let mut tasks: Vec<Task> = self
+            .tasks
+            .iter()
+            .filter(|task| task.context == context && task.finished_at.is_some())
+            .filter(|task| from.as_ref().is_none_or(|from| task.day >= *from))
+            .filter(|task| to.as_ref().is_none_or(|to| task.day <= *to))
+            .cloned()
+            .collect();
+        tasks.sort_by(|a, b| (&a.day, a.position).cmp(&(&b.day, b.position)));
+        Ok(tasks)
+    }
+
+    fn insert_task(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        task: &NewTask,
+    ) -> Result<()> {
+        for other in self.day_tasks_mut(context, day) {
+            if other.position >= position {
+                other.position += 1;
+            }
+        }
+        self.last_task_id += 1;
+        self.tasks.push(Task {
+            id: self.last_task_id,
+            uuid: task.uuid.clone(),
+            description: task.description.clone(),
+            created_at: task.created_at,
+            started_at: None,
+            finished_at: None,
+            context: context.to_string(),
+            day: day.format("%Y-%m-%d").to_string(),
+            position,
+            estimated_duration: task.estimated_duration,
+            fixed_at: task.fixed_at,
+            note: None,
+            source_uid: task.source_uid.clone(),
+            git: None,
+            pomodoros: 0,
+        });
+        Ok(())
+    }
+
+    fn remove_task(&mut self, context: &str, day: NaiveDate, position: u32) -> Result<()> {
+        let id = self.task_mut(context, day, position)?.id;
+        self.tasks.retain(|task| task.id != id);
+        for other in self.day_tasks_mut(context, day) {
+            if other.position > position {
+                other.position -= 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn move_task(&mut self, context: &str, day: NaiveDate, from: u32, to: u32) -> Result<()> {
+        let id = self.task_mut(context, day, from)?.id;
+        for other in self.day_tasks_mut(context, day) {
+            if other.id == id {
+                other.position = to;
+            } else if from < to && other.position > from && other.position <= to {
+                other.position -= 1;
+            } else if from > to && other.position >= to && other.position < from {
+                other.position += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_started(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        started_at: Option<DateTime<Local>>,
+    ) -> Result<()> {
+        self.task_mut(context, day, position)?.started_at = started_at;
+        Ok(())
+    }
+
+    fn set_finished(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        finished_at: Option<DateTime<Local>>,
+    ) -> Result<()> {
+        self.task_mut(context, day, position)?.finished_at = finished_at;
+        Ok(())
+    }
+
+    fn set_git(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        git: Option<&GitContext>,
+    ) -> Result<()> {
+        self.task_mut(context, day, position)?.git = git.cloned();
+        Ok(())
+    }
+
+    fn set_pomodoros(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        pomodoros: u32,
+    ) -> Result<()> {
+        self.task_mut(context, day, position)?.pomodoros = pomodoros;
+        Ok(())
+    }
+
+    fn add_note(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        line: &str,
+    ) -> Result<()> {
+        let task = self.task_mut(context, day, position)?;
+        task.note = Some(match task.note.take() {
+            Some(note) => format!("{}\n{}", note, line),
+            None => line.to_string(),
+        });
+        Ok(())
+    }
+
+    fn work_events(&self, context: &str, day: NaiveDate) -> Result<Vec<DateTime<Local>>> {
+        Ok(self
+            .work
+            .iter()
+            .filter(|(work_context, work_day, _)| work_context == context && *work_day == day)
+            .map(|(_, _, at)| *at)
+            .collect())
+    }
+
+    fn add_work_event(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        at: DateTime<Local>,
+    ) -> Result<()> {
+        self.work.push((context.to_string(), day, at));
+        Ok(())
+    }
+
+    fn end_of_work(&self, day: NaiveDate) -> Result<Option<NaiveTime>> {
+        Ok(self.end_of_work.get(&day).copied())
+    }
+
+    fn set_end_of_work(&mut self, day: NaiveDate, end_of_work: NaiveTime) -> Result<()> {
+        self.end_of_work.insert(day, end_of_work);
+        Ok(())
+    }
+
+    fn planned_breaks(&self, day: NaiveDate) -> Result<Vec<PlannedBreak>> {
+        let mut breaks: Vec<PlannedBreak> = self
+            .breaks
+            .iter()
+            .filter(|(break_day, _)| *break_day == day)
+            .map(|(_, planned_break)| planned_break.clone())
+            .collect();
+        breaks.sort_by_key(|planned_break| planned_break.start);
+        Ok(breaks)
+    }
+
+    fn add_planned_break(
+        &mut self,
+        day: NaiveDate,
+        start: NaiveTime,
+        duration: Duration,
+    ) -> Result<()> {
+        self.last_break_id += 1;
+        self.breaks.push((
+            day,
+            PlannedBreak {
+                id: self.last_break_id,
+                start,
+                duration,
+            },
+        ));
+        Ok(())
+    }
+
+    fn remove_planned_break(&mut self, day: NaiveDate, id: u32) -> Result<()> {
+        self.breaks
+            .retain(|(break_day, planned_break)| !(*break_day == day && planned_break.id == id));
+        Ok(())
+    }
+
+    fn setting(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.settings.get(key).cloned())
+    }
+
+    fn set_setting(&mut self, _day: NaiveDate, key: &str, value: &str) -> Result<()> {
+        self.settings.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn settings(&self) -> Result<Vec<(String, String)>> {
+        let mut settings: Vec<(String, String)> = self
+            .settings
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        settings.sort();
+        Ok(settings)
+    }
+
+    fn queued_events(&self) -> Result<Vec<QueuedEvent>> {
+        Ok(self.events.clone())
+    }
+
+    fn queue_event(&mut self, _day: NaiveDate, url: &str, body: &str) -> Result<()> {
+        self.last_event_id += 1;
+        self.events.push(QueuedEvent {
+            id: self.last_event_id,
+            url: url.to_string(),
+            body: body.to_string(),
+        });
+        Ok(())
+    }
+
+    fn remove_queued_event(&mut self, _day: NaiveDate, id: u32) -> Result<()> {
+        self.events.retain(|event| event.id != id);
+        Ok(())
+    }
+}
@@ -0,0 +1,280 @@
+// Where the journal is kept. A 'Storage' saves the tasks and the work
+// events of each day, and the breaks and end of work planned for it;
+// the 'Journal' decides what to save. Operations address tasks by
+// context, day and position, as the positions of a day never have
+// gaps. Each context is a list of its own, with its own tasks and
+// work events; the planned breaks and end of work are shared. The
+// events that could not be sent to webhooks yet are kept too.
+
+use crate::model::{GitContext, PlannedBreak, Task};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime};
+use std::path::Path;
+use std::str::FromStr;
+
+mod memory;
+mod sqlite;
+mod text;
+
+pub use memory::MemoryStorage;
+pub use sqlite::SqliteStorage;
+pub use text::TextStorage;
+
+/// The context of the tasks of journals without contexts.
+pub const DEFAULT_CONTEXT: &str = "default";
+
+/// The setting keeping the context used when none is given.
+pub const CONTEXT_SETTING: &str = "context";
+
+/// A task to insert in the journal.
+#[derive(Debug)]
+pub struct NewTask {
+    pub uuid: String,
+    pub description: String,
+    pub estimated_duration: Duration,
+    pub fixed_at: Option<NaiveTime>,
+    pub source_uid: Option<String>,
+    pub created_at: DateTime<Local>,
+}
+
+/// An event waiting to be sent to a webhook.
+#[derive(Debug, Clone)]
+pub struct QueuedEvent {
+    pub id: u32,
+    pub url: String,
+    /// The JSON to post.
+    pub body: String,
+}
+
+pub trait Storage {
+    /// The contexts with tasks or work events, by name.
+    fn contexts(&self) -> Result<Vec<String>>;
+
+    /// The days with tasks, work events, or a planned end of work or
+    /// break, in any context, in order.
+    fn days(&self) -> Result<Vec<NaiveDate>>;
+
+    /// The tasks of a day, by position.
+    fn tasks(&self, context: &str, day: NaiveDate) -> Result<Vec<Task>>;
+
+    /// The finished tasks of the days between 'from' and 'to' (included,
+    /// when given), by day and position.
+    fn finished_tasks(
+        &self,
+        context: &str,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Result<Vec<Task>>;
+
+    /// Inserts a task at the given position, between 1 and N+1, shifting
+    /// the tasks from that position to the right.
+    fn insert_task(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        task: &NewTask,
+    ) -> Result<()>;
+
+    /// Removes the task at the given position, shifting the tasks after
+    /// it to the left.
+    fn remove_task(&mut self, context: &str, day: NaiveDate, position: u32) -> Result<()>;
+
+    /// Moves the task at position 'from' to position 'to', shifting the
+    /// tasks in between.
+    fn move_task(&mut self, context: &str, day: NaiveDate, from: u32, to: u32) -> Result<()>;
+
+    fn set_started(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        started_at: Option<DateTime<Local>>,
+    ) -> Result<()>;
+
+    fn set_finished(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        finished_at: Option<DateTime<Local>>,
+    ) -> Result<()>;
+
+    /// Sets the git repository the task was worked in.
+    fn set_git(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        git: Option<&GitContext>,
+    ) -> Result<()>;
+
+    /// Sets the number of pomodoros completed on a task.
+    fn set_pomodoros(&mut self, context: &str, day: NaiveDate, position: u32, pomodoros: u32)
+        -> Result<()>;
+
+    /// Appends a line to the note of a task.
+    fn add_note(&mut self, context: &str, day: NaiveDate, position: u32, line: &str)
+        -> Result<()>;
+
+    /// The times at which work was started or stopped during a day, in
+    /// order. Work is running after the first one.
+    fn work_events(&self, context: &str, day: NaiveDate) -> Result<Vec<DateTime<Local>>>;
+
+    fn add_work_event(&mut self, context: &str, day: NaiveDate, at: DateTime<Local>)
+        -> Result<()>;
+
+    /// The end of work planned for a day, if any.
+    fn end_of_work(&self, day: NaiveDate) -> Result<Option<NaiveTime>>;
+
+    fn set_end_of_work(&mut self, day: NaiveDate, end_of_work: NaiveTime) -> Result<()>;
+
+    /// The breaks planned for a day, by start time.
+    fn planned_breaks(&self, day: NaiveDate) -> Result<Vec<PlannedBreak>>;
+
+    fn add_planned_break(&mut self, day: NaiveDate, start: NaiveTime, duration: Duration)
+        -> Result<()>;
+
+    fn remove_planned_break(&mut self, day: NaiveDate, id: u32) -> Result<()>;
+
+    /// A setting of the journal, like the current context.
+    fn setting(&self, key: &str) -> Result<Option<String>>;
+
+    /// Changes a setting. The day is the one of the change, for the
+    /// backends that log the changes by day.
+    fn set_setting(&mut self, day: NaiveDate, key: &str, value: &str) -> Result<()>;
+
+    /// All the settings, by key.
+    fn settings(&self) -> Result<Vec<(String, String)>>;
+
+    /// The events not sent yet, in the order they were queued.
+    fn queued_events(&self) -> Result<Vec<QueuedEvent>>;
+
+    /// Queues an event, or removes it, on the given day like
+    /// 'set_setting'.
+    fn queue_event(&mut self, day: NaiveDate, url: &str, body: &str) -> Result<()>;
+
+    fn remove_queued_event(&mut self, day: NaiveDate, id: u32) -> Result<()>;
+}
+
+/// The kinds of storage.
+#[derive(Debug, Clone, Copy)]
+pub enum Backend {
+    Sqlite,
+    /// Lost when the journal is closed.
+    Memory,
+    Text,
+}
+
+impl Backend {
+    /// Guesses the backend of a journal from its path: text files end
+    /// with ".txt", and ":memory:" is kept in memory.
+    pub fn from_path(path: &Path) -> Backend {
+        if path == Path::new(":memory:") {
+            return Backend::Memory;
+        }
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("txt") => Backend::Text,
+            _ => Backend::Sqlite,
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Backend> {
+        match s {
+            "sqlite" => Ok(Backend::Sqlite),
+            "memory" => Ok(Backend::Memory),
+            "text" => Ok(Backend::Text),
+            _ => anyhow::bail!("Unknown backend {}, expected 'sqlite', 'memory' or 'text'.", s),
+        }
+    }
+}
+
+/// Copies all the days of a journal to another, which is expected to
+/// be empty, with the settings and the events queued for webhooks.
+/// Task, break and event ids are not kept, but numbered again in the
+/// same order. The settings and events are changed on 'today'. Returns
+/// the number of days copied.
+pub fn copy(from: &dyn Storage, to: &mut dyn Storage, today: NaiveDate) -> Result<usize> {
+    let contexts = from.contexts()?;
+    let days = from.days()?;
+    for &day in &days {
+        for context in &contexts {
+            copy_tasks(from, to, context, day)?;
+        }
+        if let Some(end_of_work) = from.end_of_work(day)? {
+            to.set_end_of_work(day, end_of_work)?;
+        }
+        for planned_break in from.planned_breaks(day)? {
+            to.add_planned_break(day, planned_break.start, planned_break.duration)?;
+        }
+    }
+    for (key, value) in from.settings()? {
+        to.set_setting(today, &key, &value)?;
+    }
+    for event in from.queued_events()? {
+        to.queue_event(today, &event.url, &event.body)?;
+    }
+    Ok(days.len())
+}
+
+/// Copies the tasks and work events of a day in a context.
+fn copy_tasks(from: &dyn Storage, to: &mut dyn Storage, context: &str, day: NaiveDate) -> Result<()> {
+    for task in from.tasks(context, day)? {
+        let new_task = NewTask {
+            uuid: task.uuid.clone(),
+            description: task.description.clone(),
+            estimated_duration: task.estimated_duration,
+            fixed_at: task.fixed_at,
+            source_uid: task.source_uid.clone(),
+            created_at: task.created_at,
+        };
+        to.insert_task(context, day, task.position, &new_task)?;
+        if task.started_at.is_some() {
+            to.set_started(context, day, task.position, task.started_at)?;
+        }
+        if task.finished_at.is_some() {
+            to.set_finished(context, day, task.position, task.finished_at)?;
+        }
+        if let Some(note) = &task.note {
+            to.add_note(context, day, task.position, note)?;
+        }
+        if task.git.is_some() {
+            to.set_git(context, day, task.position, task.git.as_ref())?;
+        }
+        if task.pomodoros > 0 {
+            to.set_pomodoros(context, day, task.position, task.pomodoros)?;
+        }
+    }
+    for at in from.work_events(context, day)? {
+        to.add_work_event(context, day, at)?;
+    }
+    Ok(())
+}
+
+/// Opens the journal at the given path with the given backend, or the
+/// one guessed from the path.
+pub fn open(path: &Path, backend: Option<Backend>) -> Result<Box<dyn Storage>> {
+    Ok(match backend.unwrap_or_else(|| Backend::from_path(path)) {
+        Backend::Sqlite => Box::new(SqliteStorage::open(path)?),
+        Backend::Memory => Box::new(MemoryStorage::default()),
+        Backend::Text => Box::new(TextStorage::open(path)?),
+    })
+}
+
+/// Opens an existing journal to read it, without changing it: see
+/// 'SqliteStorage::open_read_only'. Text journals are only written by
+/// their changes.
+pub fn open_read_only(path: &Path, backend: Option<Backend>) -> Result<Box<dyn Storage>> {
+    if !path.exists() {
+        bail!("{} does not exist.", path.display());
+    }
+    Ok(match backend.unwrap_or_else(|| Backend::from_path(path)) {
+        Backend::Sqlite => Box::new(SqliteStorage::open_read_only(path)?),
+        Backend::Memory => Box::new(MemoryStorage::default()),
+        Backend::Text => Box::new(TextStorage::open(path)?),
+    })
+}
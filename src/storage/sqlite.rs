@@ -0,0 +1,636 @@
+// The SQLite journal: the tasks, the work table with the times work
+// was started and stopped, and the settings and breaks planned for each
+// day. The schema is upgraded with MIGRATIONS when the journal is
+// opened.
+
+use crate::model::{GitContext, PlannedBreak, Task};
+use crate::storage::{NewTask, QueuedEvent, Storage};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime};
+use rusqlite::types::Type;
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Row};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// The columns to select to build a task with 'task_from_row'.
+const TASK_COLUMNS: &str = "id, day, description, position, created_at, started_at, finished_at, estimated_duration, fixed_at, note, source_uid, uuid, context, git, pomodoros";
+
+/// Schema changes applied to existing journals, in order. The
+/// 'user_version' of the journal database counts the ones already
+/// applied.
+const MIGRATIONS: &[&str] = &[
+    "ALTER TABLE task ADD COLUMN fixed_at TEXT;",
+    "CREATE TABLE day (day TEXT PRIMARY KEY, end_of_work TEXT);",
+    "CREATE TABLE planned_break (
+         id              INTEGER PRIMARY KEY AUTOINCREMENT,
+         day             TEXT NOT NULL,
+         start           TEXT NOT NULL,
+         duration        INTEGER NOT NULL
+     );
+     CREATE INDEX planned_break_day ON planned_break (day);",
+    "ALTER TABLE task ADD COLUMN source_uid TEXT;",
+    "ALTER TABLE task ADD COLUMN note TEXT;",
+    // The UUIDs of the existing tasks are set by 'backfill_uuids'.
+    "ALTER TABLE task ADD COLUMN uuid TEXT;
+     CREATE UNIQUE INDEX task_uuid ON task (uuid);",
+    "ALTER TABLE task ADD COLUMN context TEXT NOT NULL DEFAULT 'default';
+     ALTER TABLE work ADD COLUMN context TEXT NOT NULL DEFAULT 'default';
+     DROP INDEX day_position;
+     CREATE UNIQUE INDEX day_position ON task (context, day, position);
+     CREATE TABLE setting (key TEXT PRIMARY KEY, value TEXT NOT NULL);",
+    "CREATE TABLE webhook_event (
+         id              INTEGER PRIMARY KEY AUTOINCREMENT,
+         url             TEXT NOT NULL,
+         body            TEXT NOT NULL
+     );",
+    // The git context of the task, as JSON.
+    "ALTER TABLE task ADD COLUMN git TEXT;",
+    "ALTER TABLE task ADD COLUMN pomodoros INTEGER NOT NULL DEFAULT 0;",
+    // Timestamps in UTC, all in the same format, with an explicit
+    // offset and whole seconds: the creation times of older journals
+    // were written by SQLite without an offset, and the other times
+    // by rusqlite with fractions of seconds.
+    "UPDATE task SET
+         created_at = COALESCE(strftime('%Y-%m-%d %H:%M:%S+00:00', created_at), created_at),
+         started_at = COALESCE(strftime('%Y-%m-%d %H:%M:%S+00:00', started_at), started_at),
+         finished_at = COALESCE(strftime('%Y-%m-%d %H:%M:%S+00:00', finished_at), finished_at);
+     UPDATE work SET
+         timestamp = COALESCE(strftime('%Y-%m-%d %H:%M:%S+00:00', timestamp), timestamp);",
+];
+
+pub struct SqliteStorage {
+    db: Connection,
+}
+
+impl SqliteStorage {
+    /// Opens the journal database at the given path, creating it if it
+    /// does not exist.
+    pub fn open(path: &Path) -> Result<SqliteStorage> {
+        let journal_exists = path.exists();
+        let db = Connection::open(path)
+            .with_context(|| format!("Failed to open journal {}.", path.display()))?;
+        if !journal_exists {
+            init_journal(&db)?;
+        }
+        migrate_journal(&db)?;
+        Ok(SqliteStorage { db })
+    }
+
+    /// Opens an existing journal database without changing it. Journals
+    /// of other versions are refused, as they cannot be read without
+    /// migrating them.
+    pub fn open_read_only(path: &Path) -> Result<SqliteStorage> {
+        let db = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .with_context(|| format!("Failed to open journal {}.", path.display()))?;
+        let version = journal_version(&db)?;
+        if version < MIGRATIONS.len() {
+            bail!(
+                "Journal {} was written by an older version of akiv. Run any command on it \
+                 first, like 'akiv -j {} list', to upgrade it.",
+                path.display(),
+                path.display()
+            );
+        }
+        if version > MIGRATIONS.len() {
+            bail!(
+                "Journal {} was written by a newer version of akiv.",
+                path.display()
+            );
+        }
+        Ok(SqliteStorage { db })
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn contexts(&self) -> Result<Vec<String>> {
+        let mut stmt = self
+            .db
+            .prepare("SELECT context FROM task UNION SELECT context FROM work ORDER BY context")
+            .context("Failed to fetch contexts from database.")?;
+        let mapped_rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+
+        let mut contexts = Vec::new();
+        for context in mapped_rows {
+            contexts.push(context?);
+        }
+        Ok(contexts)
+    }
+
+    fn days(&self) -> Result<Vec<NaiveDate>> {
+        let mut stmt = self
+            .db
+            .prepare("SELECT day FROM task UNION SELECT day FROM work UNION SELECT day FROM day UNION SELECT day FROM planned_break ORDER BY day")
+            .context("Failed to fetch days from database.")?;
+        let mapped_rows = stmt.query_map([], |row| row.get::<_, NaiveDate>(0))?;
+
+        let mut days = Vec::new();
+        for day in mapped_rows {
+            days.push(day?);
+        }
+        Ok(days)
+    }
+
+    fn tasks(&self, context: &str, day: NaiveDate) -> Result<Vec<Task>> {
+        let mut stmt = self.db.prepare(&format!(
+            "SELECT {} FROM task WHERE context = ?1 AND day = ?2 ORDER BY position",
+            TASK_COLUMNS
+        ))?;
+        let mapped_rows = stmt.query_map(params![context, day], task_from_row)?;
+
+        let mut tasks = Vec::new();
+        for task in mapped_rows {
+            tasks.push(task?);
+        }
+
+        Ok(tasks)
+    }
+
+    fn finished_tasks(
+        &self,
+        context: &str,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Result<Vec<Task>> {
+        let mut stmt = self.db.prepare(&format!(
+            "SELECT {} FROM task WHERE context = ?1 AND finished_at IS NOT NULL AND (?2 IS NULL OR day >= ?2) AND (?3 IS NULL OR day <= ?3) ORDER BY day, position",
+            TASK_COLUMNS
+        ))?;
+        let mapped_rows = stmt.query_map(params![context, from, to], task_from_row)?;
+
+        let mut tasks = Vec::new();
+        for task in mapped_rows {
+            tasks.push(task?);
+        }
+
+        Ok(tasks)
+    }
+
+    fn insert_task(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        task: &NewTask,
+    ) -> Result<()> {
+        // hack to shift all positions after the insert to the right without breaking the unique constraint.
+        self.db.execute("UPDATE task set position = - (position + 1) where context = ?1 and day = ?2 and position >= ?3",
+                        params![context, day, position])
+            .context("Failed to shift tasks to the right in database.")?;
+
+        self.db.execute("UPDATE task set position = - position where context = ?1 and day = ?2 and position < 0", params![context, day])
+            .context("Failed to shift tasks to the right in database.")?;
+
+        self.db.execute("INSERT INTO task (context, day, description, position, created_at, estimated_duration, fixed_at, source_uid, uuid) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                        params![context, day, task.description, position, task.created_at, task.estimated_duration.num_seconds(), task.fixed_at, task.source_uid, task.uuid])
+            .context("Failed to insert task to database.")?;
+        Ok(())
+    }
+
+    fn remove_task(&mut self, context: &str, day: NaiveDate, position: u32) -> Result<()> {
+        self.db
+            .execute(
+                "DELETE FROM task where context = ?1 and day = ?2 and position = ?3",
+                params![context, day, position],
+            )
+            .context("Failed to remove tasks from database.")?;
+
+        // hack to shift all positions after the remove to the left without breaking the unique constraint.
+        self.db.execute("UPDATE task set position = - (position - 1) where context = ?1 and day = ?2 and position > ?3", params![context, day, position])
+            .context("Failed to shift tasks to the left")?;
+        self.db.execute("UPDATE task set position = - position  where context = ?1 and day = ?2 and position < 0", params![context, day])
+            .context("Failed to shift tasks to the left")?;
+        Ok(())
+    }
+
+    fn move_task(&mut self, context: &str, day: NaiveDate, from: u32, to: u32) -> Result<()> {
+        if from == to {
+            return Ok(());
+        }
+
+        // Put the task aside, then use the same hack as when adding and
+        // removing tasks to shift the tasks in between without breaking
+        // the unique constraint.
+        self.db
+            .execute(
+                "UPDATE task set position = 0 where context = ?1 and day = ?2 and position = ?3",
+                params![context, day, from],
+            )
+            .context("Failed to move task in database.")?;
+        if from < to {
+            self.db.execute("UPDATE task set position = - (position - 1) where context = ?1 and day = ?2 and position > ?3 and position <= ?4", params![context, day, from, to])
+                .context("Failed to shift tasks to the left")?;
+        } else {
+            self.db.execute("UPDATE task set position = - (position + 1) where context = ?1 and day = ?2 and position >= ?4 and position < ?3", params![context, day, from, to])
+                .context("Failed to shift tasks to the right")?;
+        }
+        self.db.execute("UPDATE task set position = - position where context = ?1 and day = ?2 and position < 0", params![context, day])
+            .context("Failed to shift tasks")?;
+        self.db
+            .execute(
+                "UPDATE task set position = ?3 where context = ?1 and day = ?2 and position = 0",
+                params![context, day, to],
+            )
+            .context("Failed to move task in database.")?;
+        Ok(())
+    }
+
+    fn set_started(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        started_at: Option<DateTime<Local>>,
+    ) -> Result<()> {
+        self.db
+            .execute(
+                "UPDATE task set started_at = ?4 where context = ?1 and day = ?2 and position = ?3",
+                params![context, day, position, started_at],
+            )
+            .context("Failed to start task in the database")?;
+        Ok(())
+    }
+
+    fn set_finished(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        finished_at: Option<DateTime<Local>>,
+    ) -> Result<()> {
+        self.db
+            .execute(
+                "UPDATE task set finished_at = ?4 where context = ?1 and day = ?2 and position = ?3",
+                params![context, day, position, finished_at],
+            )
+            .context("Failed to finish task in the database")?;
+        Ok(())
+    }
+
+    fn set_git(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        git: Option<&GitContext>,
+    ) -> Result<()> {
+        let git = git.map(serde_json::to_string).transpose()?;
+        self.db
+            .execute(
+                "UPDATE task set git = ?4 where context = ?1 and day = ?2 and position = ?3",
+                params![context, day, position, git],
+            )
+            .context("Failed to set the git context of the task in the database")?;
+        Ok(())
+    }
+
+    fn set_pomodoros(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        pomodoros: u32,
+    ) -> Result<()> {
+        self.db
+            .execute(
+                "UPDATE task set pomodoros = ?4 where context = ?1 and day = ?2 and position = ?3",
+                params![context, day, position, pomodoros],
+            )
+            .context("Failed to set the pomodoros of the task in the database")?;
+        Ok(())
+    }
+
+    fn add_note(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        line: &str,
+    ) -> Result<()> {
+        self.db
+            .execute(
+                "UPDATE task set note = COALESCE(note || char(10), '') || ?4 where context = ?1 and day = ?2 and position = ?3",
+                params![context, day, position, line],
+            )
+            .context("Failed to add note to the task in the database")?;
+        Ok(())
+    }
+
+    fn work_events(&self, context: &str, day: NaiveDate) -> Result<Vec<DateTime<Local>>> {
+        let mut stmt = self
+            .db
+            .prepare("SELECT timestamp FROM work WHERE context = ?1 AND day = ?2 ORDER BY id ASC")
+            .context("Failed to fetch work from database.")?;
+        let mapped_rows = stmt
+            .query_map(params![context, day], |row| row.get::<_, DateTime<Local>>(0))
+            .context("Failed to fetch work from database.")?;
+
+        let mut events = Vec::new();
+        for event in mapped_rows {
+            events.push(event?);
+        }
+        Ok(events)
+    }
+
+    fn add_work_event(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        at: DateTime<Local>,
+    ) -> Result<()> {
+        self.db
+            .execute(
+                "INSERT INTO work (context, day, timestamp) VALUES(?1, ?2, ?3)",
+                params![context, day, at],
+            )
+            .context("Failed to insert entry to the work table.")?;
+        Ok(())
+    }
+
+    fn end_of_work(&self, day: NaiveDate) -> Result<Option<NaiveTime>> {
+        let end_of_work = self
+            .db
+            .query_row(
+                "SELECT end_of_work FROM day WHERE day = ?1",
+                params![day],
+                |row| row.get::<_, Option<NaiveTime>>(0),
+            )
+            .optional()
+            .context("Failed to get the end of work from database.")?;
+        Ok(end_of_work.flatten())
+    }
+
+    fn set_end_of_work(&mut self, day: NaiveDate, end_of_work: NaiveTime) -> Result<()> {
+        self.db.execute(
+            "INSERT INTO day (day, end_of_work) VALUES(?1, ?2) ON CONFLICT(day) DO UPDATE SET end_of_work = ?2",
+            params![day, end_of_work],
+        )
+        .context("Failed to set the end of work in database.")?;
+        Ok(())
+    }
+
+    fn planned_breaks(&self, day: NaiveDate) -> Result<Vec<PlannedBreak>> {
+        let mut stmt = self
+            .db
+            .prepare("SELECT id, start, duration FROM planned_break WHERE day = ?1 ORDER BY start")
+            .context("Failed to fetch planned breaks from database.")?;
+        let mapped_rows = stmt.query_map(params![day], |row| {
+            Ok(PlannedBreak {
+                id: row.get(0)?,
+                start: row.get(1)?,
+                duration: Duration::seconds(row.get::<_, i64>(2)?),
+            })
+        })?;
+
+        let mut breaks = Vec::new();
+        for planned_break in mapped_rows {
+            breaks.push(planned_break?);
+        }
+        Ok(breaks)
+    }
+
+    fn add_planned_break(
+        &mut self,
+        day: NaiveDate,
+        start: NaiveTime,
+        duration: Duration,
+    ) -> Result<()> {
+        self.db
+            .execute(
+                "INSERT INTO planned_break (day, start, duration) VALUES(?1, ?2, ?3)",
+                params![day, start, duration.num_seconds()],
+            )
+            .context("Failed to insert planned break to database.")?;
+        Ok(())
+    }
+
+    fn remove_planned_break(&mut self, day: NaiveDate, id: u32) -> Result<()> {
+        self.db
+            .execute(
+                "DELETE FROM planned_break WHERE day = ?1 AND id = ?2",
+                params![day, id],
+            )
+            .context("Failed to remove planned break from database.")?;
+        Ok(())
+    }
+
+    fn setting(&self, key: &str) -> Result<Option<String>> {
+        let value = self
+            .db
+            .query_row(
+                "SELECT value FROM setting WHERE key = ?1",
+                params![key],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .context("Failed to get setting from database.")?;
+        Ok(value)
+    }
+
+    fn set_setting(&mut self, _day: NaiveDate, key: &str, value: &str) -> Result<()> {
+        self.db.execute(
+            "INSERT INTO setting (key, value) VALUES(?1, ?2) ON CONFLICT(key) DO UPDATE SET value = ?2",
+            params![key, value],
+        )
+        .context("Failed to set setting in database.")?;
+        Ok(())
+    }
+
+    fn settings(&self) -> Result<Vec<(String, String)>> {
+        let mut stmt = self
+            .db
+            .prepare("SELECT key, value FROM setting ORDER BY key")
+            .context("Failed to fetch settings from database.")?;
+        let mapped_rows =
+            stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+
+        let mut settings = Vec::new();
+        for setting in mapped_rows {
+            settings.push(setting?);
+        }
+        Ok(settings)
+    }
+
+    fn queued_events(&self) -> Result<Vec<QueuedEvent>> {
+        let mut stmt = self
+            .db
+            .prepare("SELECT id, url, body FROM webhook_event ORDER BY id")
+            .context("Failed to fetch queued events from database.")?;
+        let mapped_rows = stmt.query_map([], |row| {
+            Ok(QueuedEvent {
+                id: row.get(0)?,
+                url: row.get(1)?,
+                body: row.get(2)?,
+            })
+        })?;
+
+        let mut events = Vec::new();
+        for event in mapped_rows {
+            events.push(event?);
+        }
+        Ok(events)
+    }
+
+    fn queue_event(&mut self, _day: NaiveDate, url: &str, body: &str) -> Result<()> {
+        self.db
+            .execute(
+                "INSERT INTO webhook_event (url, body) VALUES(?1, ?2)",
+                params![url, body],
+            )
+            .context("Failed to queue event in database.")?;
+        Ok(())
+    }
+
+    fn remove_queued_event(&mut self, _day: NaiveDate, id: u32) -> Result<()> {
+        self.db
+            .execute("DELETE FROM webhook_event WHERE id = ?1", params![id])
+            .context("Failed to remove queued event from database.")?;
+        Ok(())
+    }
+}
+
+/// Initialize the journal database.
+fn init_journal(db: &Connection) -> Result<()> {
+    db.execute(
+        "CREATE TABLE if not exists task (
+                  id              INTEGER PRIMARY KEY AUTOINCREMENT,
+                  day             TEXT NOT NULL,
+                  description     TEXT NOT NULL,
+                  position        INTEGER NOT NULL,
+                  created_at      TEXT NOT NULL,
+                  started_at      TEXT,
+                  finished_at     TEXT,
+                  estimated_duration  INTEGER NOT NULL
+                  )",
+        [],
+    )
+    .context("Faied to create task table.")?;
+
+    db.execute(
+        "CREATE UNIQUE INDEX day_position ON task (day, position)",
+        [],
+    )
+    .context("Failed to create unique index on task table.")?;
+
+    db.execute(
+        "CREATE TABLE if not exists work (
+                  id              INTEGER PRIMARY KEY AUTOINCREMENT,
+                  day             TEXT NOT NULL,
+                  timestamp       TEXT
+                  )",
+        [],
+    )
+    .context("Failed to create work table.")?;
+
+    db.execute("CREATE INDEX day_index ON work (day)", [])
+        .context("Failed to create unique index on work table.")?;
+
+    Ok(())
+}
+
+/// Apply the pending migrations to the journal database.
+fn migrate_journal(db: &Connection) -> Result<()> {
+    let version = journal_version(db)?;
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        db.execute_batch(&format!(
+            "BEGIN; {} PRAGMA user_version = {}; COMMIT;",
+            migration,
+            index + 1
+        ))
+        .with_context(|| format!("Failed to migrate journal to version {}.", index + 1))?;
+    }
+    backfill_uuids(db)?;
+
+    Ok(())
+}
+
+/// The number of migrations applied to the journal database.
+fn journal_version(db: &Connection) -> Result<usize> {
+    db.query_row("PRAGMA user_version", [], |row| row.get::<_, usize>(0))
+        .context("Failed to read journal version.")
+}
+
+/// Gives the tasks from before UUIDs one derived from their day,
+/// creation time and description, so that the copies of a journal
+/// migrated on different machines can still be merged. These tasks all
+/// belong to the default context.
+fn backfill_uuids(db: &Connection) -> Result<()> {
+    let tasks = {
+        let mut stmt = db
+            .prepare("SELECT id, day, created_at, description FROM task WHERE uuid IS NULL ORDER BY day, position")
+            .context("Failed to fetch tasks without uuid from database.")?;
+        let mapped_rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, u32>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?;
+        mapped_rows.collect::<rusqlite::Result<Vec<_>>>()?
+    };
+    if tasks.is_empty() {
+        return Ok(());
+    }
+
+    let transaction = db.unchecked_transaction()?;
+    let mut uuids = HashSet::new();
+    for (id, day, created_at, description) in tasks {
+        // Tasks alike but for their position are told apart by a count.
+        let mut uuid = derived_uuid(&[&day, &created_at, &description]);
+        let mut count = 0;
+        while !uuids.insert(uuid.clone()) {
+            count += 1;
+            uuid = derived_uuid(&[&day, &created_at, &description, &count.to_string()]);
+        }
+        transaction
+            .execute("UPDATE task SET uuid = ?1 WHERE id = ?2", params![uuid, id])
+            .context("Failed to set task uuid in database.")?;
+    }
+    transaction.commit()?;
+    Ok(())
+}
+
+/// A version 8 UUID made of the 128 bits FNV-1a hash of the given
+/// fields, the same on every machine.
+fn derived_uuid(fields: &[&str]) -> String {
+    let mut hash: u128 = 0x6c62272e07bb014262b821756295c58d;
+    for field in fields {
+        for byte in field.bytes().chain(std::iter::once(0)) {
+            hash ^= u128::from(byte);
+            hash = hash.wrapping_mul(0x0000000001000000000000000000013b);
+        }
+    }
+    uuid::Builder::from_custom_bytes(hash.to_be_bytes())
+        .into_uuid()
+        .to_string()
+}
+
+/// Return a task from a row in this order: [id, day, description,
+/// position, created_at, started_at, finished_at, estimated_duration,
+/// fixed_at, note, source_uid, uuid, context, git, pomodoros]
+fn task_from_row(row: &Row) -> rusqlite::Result<Task> {
+    let task = Task {
+        id: row.get(0)?,
+        day: row.get(1)?,
+        description: row.get(2)?,
+        position: row.get::<_, u32>(3)?,
+        created_at: row.get::<_, DateTime<Local>>(4)?,
+        started_at: row.get::<_, DateTime<Local>>(5).ok(),
+        finished_at: row.get::<_, DateTime<Local>>(6).ok(),
+        estimated_duration: Duration::seconds(row.get::<_, i64>(7)?),
+        fixed_at: row.get::<_, Option<NaiveTime>>(8)?,
+        note: row.get(9)?,
+        source_uid: row.get(10)?,
+        uuid: row.get(11)?,
+        context: row.get(12)?,
+        git: match row.get::<_, Option<String>>(13)? {
+            Some(git) => Some(serde_json::from_str(&git).map_err(|error| {
+                rusqlite::Error::FromSqlConversionFailure(13, Type::Text, Box::new(error))
+            })?),
+            None => None,
+        },
+        pomodoros: row.get(14)?,
+    };
+    Ok(task)
+}
@@ -0,0 +1,452 @@
+// A journal kept as a plain text log, that can be read, diffed and
+// synced like any other text file. Every change appends a line, and
+// the journal is what replaying the lines in order gives:
+//
+//     2021-05-03 add 1 30m uuid=4b1f6e4c-... created=2021-05-03T06:55:02+00:00 -- Write report
+//     2021-05-03 work 2021-05-03T07:00:00+00:00
+//     2021-05-03 start 1 2021-05-03T07:00:00+00:00
+//     2021-05-03 note 1 -- Sent the draft
+//     2021-05-03 git 1 -- {"repository":"/home/me/report","branch":"main",...}
+//     2021-05-03 finish 1 2021-05-03T07:41:10+00:00
+//
+// Timestamps are written in UTC, and read with any offset.
+// Tasks are designated by their position when the line was written.
+// The changes to the tasks and work of a context other than the
+// default one name it after the day, like "2021-05-03 @personal add
+// ...". Settings and the events queued for webhooks are written on the
+// day they change. Empty lines and lines starting with '#' are ignored.
+
+use crate::config::{parse_day, parse_duration, parse_time};
+use crate::model::{GitContext, PlannedBreak, Task};
+use crate::storage::{MemoryStorage, NewTask, QueuedEvent, Storage, DEFAULT_CONTEXT};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime, Timelike, Utc};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const HEADER: &str = "# akiv journal\n";
+
+pub struct TextStorage {
+    path: PathBuf,
+    journal: MemoryStorage,
+}
+
+impl TextStorage {
+    /// Reads the journal at the given path, creating it if it does not
+    /// exist.
+    pub fn open(path: &Path) -> Result<TextStorage> {
+        let mut journal = MemoryStorage::default();
+        if path.exists() {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read journal {}.", path.display()))?;
+            for (number, line) in content.lines().enumerate() {
+                if line.trim().is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                apply(&mut journal, line).with_context(|| {
+                    format!("Invalid line {} of journal {}.", number + 1, path.display())
+                })?;
+            }
+        } else {
+            std::fs::write(path, HEADER)
+                .with_context(|| format!("Failed to create journal {}.", path.display()))?;
+        }
+
+        Ok(TextStorage {
+            path: path.to_path_buf(),
+            journal,
+        })
+    }
+
+    /// Applies an operation on the tasks or work of a context, and
+    /// appends it to the file.
+    fn append_in(&mut self, context: &str, day: NaiveDate, operation: String) -> Result<()> {
+        if context == DEFAULT_CONTEXT {
+            self.append(day, operation)
+        } else {
+            self.append(day, format!("@{} {}", escape_word(context), operation))
+        }
+    }
+
+    /// Applies an operation, and appends it to the file.
+    fn append(&mut self, day: NaiveDate, operation: String) -> Result<()> {
+        let line = format!("{} {}", day.format("%Y-%m-%d"), operation);
+        apply(&mut self.journal, &line)?;
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open journal {}.", self.path.display()))?;
+        writeln!(file, "{}", line)
+            .with_context(|| format!("Failed to write journal {}.", self.path.display()))?;
+        Ok(())
+    }
+}
+
+impl Storage for TextStorage {
+    fn contexts(&self) -> Result<Vec<String>> {
+        self.journal.contexts()
+    }
+
+    fn days(&self) -> Result<Vec<NaiveDate>> {
+        self.journal.days()
+    }
+
+    fn tasks(&self, context: &str, day: NaiveDate) -> Result<Vec<Task>> {
+        self.journal.tasks(context, day)
+    }
+
+    fn finished_tasks(
+        &self,
+        context: &str,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Result<Vec<Task>> {
+        self.journal.finished_tasks(context, from, to)
+    }
+
+    fn insert_task(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        task: &NewTask,
+    ) -> Result<()> {
+        let mut operation = format!(
+            "add {} {} uuid={} created={}",
+            position,
+            format_duration(task.estimated_duration)?,
+            task.uuid,
+            format_timestamp(task.created_at)
+        );
+        if let Some(fixed_at) = task.fixed_at {
+            operation.push_str(&format!(" fixed={}", format_time(fixed_at)));
+        }
+        if let Some(source_uid) = &task.source_uid {
+            operation.push_str(&format!(" uid={}", escape_word(source_uid)));
+        }
+        operation.push_str(&format!(" -- {}", escape_text(&task.description)));
+        self.append_in(context, day, operation)
+    }
+
+    fn remove_task(&mut self, context: &str, day: NaiveDate, position: u32) -> Result<()> {
+        self.append_in(context, day, format!("remove {}", position))
+    }
+
+    fn move_task(&mut self, context: &str, day: NaiveDate, from: u32, to: u32) -> Result<()> {
+        self.append_in(context, day, format!("move {} {}", from, to))
+    }
+
+    fn set_started(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        started_at: Option<DateTime<Local>>,
+    ) -> Result<()> {
+        self.append_in(
+            context,
+            day,
+            format!("start {} {}", position, format_optional_timestamp(started_at)),
+        )
+    }
+
+    fn set_finished(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        finished_at: Option<DateTime<Local>>,
+    ) -> Result<()> {
+        self.append_in(
+            context,
+            day,
+            format!("finish {} {}", position, format_optional_timestamp(finished_at)),
+        )
+    }
+
+    fn set_git(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        git: Option<&GitContext>,
+    ) -> Result<()> {
+        let operation = match git {
+            Some(git) => format!("git {} -- {}", position, escape_text(&serde_json::to_string(git)?)),
+            None => format!("git {} -", position),
+        };
+        self.append_in(context, day, operation)
+    }
+
+    fn set_pomodoros(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        pomodoros: u32,
+    ) -> Result<()> {
+        self.append_in(context, day, format!("pomodoros {} {}", position, pomodoros))
+    }
+
+    fn add_note(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        position: u32,
+        line: &str,
+    ) -> Result<()> {
+        self.append_in(
+            context,
+            day,
+            format!("note {} -- {}", position, escape_text(line)),
+        )
+    }
+
+    fn work_events(&self, context: &str, day: NaiveDate) -> Result<Vec<DateTime<Local>>> {
+        self.journal.work_events(context, day)
+    }
+
+    fn add_work_event(
+        &mut self,
+        context: &str,
+        day: NaiveDate,
+        at: DateTime<Local>,
+    ) -> Result<()> {
+        self.append_in(context, day, format!("work {}", format_timestamp(at)))
+    }
+
+    fn end_of_work(&self, day: NaiveDate) -> Result<Option<NaiveTime>> {
+        self.journal.end_of_work(day)
+    }
+
+    fn set_end_of_work(&mut self, day: NaiveDate, end_of_work: NaiveTime) -> Result<()> {
+        self.append(day, format!("end-of-work {}", format_time(end_of_work)))
+    }
+
+    fn planned_breaks(&self, day: NaiveDate) -> Result<Vec<PlannedBreak>> {
+        self.journal.planned_breaks(day)
+    }
+
+    fn add_planned_break(
+        &mut self,
+        day: NaiveDate,
+        start: NaiveTime,
+        duration: Duration,
+    ) -> Result<()> {
+        self.append(
+            day,
+            format!("break {} {}", format_time(start), format_duration(duration)?),
+        )
+    }
+
+    fn remove_planned_break(&mut self, day: NaiveDate, id: u32) -> Result<()> {
+        self.append(day, format!("unbreak {}", id))
+    }
+
+    fn setting(&self, key: &str) -> Result<Option<String>> {
+        self.journal.setting(key)
+    }
+
+    fn set_setting(&mut self, day: NaiveDate, key: &str, value: &str) -> Result<()> {
+        self.append(
+            day,
+            format!("set {} {}", escape_word(key), escape_word(value)),
+        )
+    }
+
+    fn settings(&self) -> Result<Vec<(String, String)>> {
+        self.journal.settings()
+    }
+
+    fn queued_events(&self) -> Result<Vec<QueuedEvent>> {
+        self.journal.queued_events()
+    }
+
+    fn queue_event(&mut self, day: NaiveDate, url: &str, body: &str) -> Result<()> {
+        self.append(
+            day,
+            format!("queue {} -- {}", escape_word(url), escape_text(body)),
+        )
+    }
+
+    fn remove_queued_event(&mut self, day: NaiveDate, id: u32) -> Result<()> {
+        self.append(day, format!("dequeue {}", id))
+    }
+}
+
+/// Applies the operation of a line to the journal.
+fn apply(journal: &mut MemoryStorage, line: &str) -> Result<()> {
+    let (operation, text) = match line.split_once(" --") {
+        Some((operation, text)) => (
+            operation,
+            Some(unescape_text(text.strip_prefix(' ').unwrap_or(text))),
+        ),
+        None => (line, None),
+    };
+    let mut words: Vec<&str> = operation.split_whitespace().collect();
+    let day = match words.first() {
+        Some(day) => parse_day(day)?,
+        None => bail!("Missing day."),
+    };
+    words.remove(0);
+    let context = match words.first().and_then(|word| word.strip_prefix('@')) {
+        Some(context) => {
+            words.remove(0);
+            unescape_word(context)
+        }
+        None => DEFAULT_CONTEXT.to_string(),
+    };
+    let context = context.as_str();
+
+    match (words.as_slice(), text) {
+        (["add", position, estimate, options @ ..], Some(description)) => {
+            let mut uuid = None;
+            let mut created_at = None;
+            let mut fixed_at = None;
+            let mut source_uid = None;
+            for option in options {
+                match option.split_once('=') {
+                    Some(("uuid", value)) => uuid = Some(value.to_string()),
+                    Some(("created", value)) => created_at = Some(parse_timestamp(value)?),
+                    Some(("fixed", value)) => fixed_at = Some(parse_time(value)?),
+                    Some(("uid", value)) => source_uid = Some(unescape_word(value)),
+                    _ => bail!("Unknown option {}.", option),
+                }
+            }
+            let task = NewTask {
+                uuid: uuid.context("Missing uuid.")?,
+                description,
+                estimated_duration: parse_duration(estimate)?,
+                fixed_at,
+                source_uid,
+                created_at: created_at.context("Missing creation time.")?,
+            };
+            journal.insert_task(context, day, position.parse()?, &task)
+        }
+        (["remove", position], None) => journal.remove_task(context, day, position.parse()?),
+        (["move", from, to], None) => {
+            journal.move_task(context, day, from.parse()?, to.parse()?)
+        }
+        (["start", position, started_at], None) => journal.set_started(
+            context,
+            day,
+            position.parse()?,
+            parse_optional_timestamp(started_at)?,
+        ),
+        (["finish", position, finished_at], None) => journal.set_finished(
+            context,
+            day,
+            position.parse()?,
+            parse_optional_timestamp(finished_at)?,
+        ),
+        (["note", position], Some(line)) => {
+            journal.add_note(context, day, position.parse()?, &line)
+        }
+        (["git", position], Some(git)) => journal.set_git(
+            context,
+            day,
+            position.parse()?,
+            Some(&serde_json::from_str(&git)?),
+        ),
+        (["git", position, "-"], None) => journal.set_git(context, day, position.parse()?, None),
+        (["pomodoros", position, pomodoros], None) => {
+            journal.set_pomodoros(context, day, position.parse()?, pomodoros.parse()?)
+        }
+        (["work", at], None) => journal.add_work_event(context, day, parse_timestamp(at)?),
+        (["end-of-work", end_of_work], None) => {
+            journal.set_end_of_work(day, parse_time(end_of_work)?)
+        }
+        (["break", start, duration], None) => {
+            journal.add_planned_break(day, parse_time(start)?, parse_duration(duration)?)
+        }
+        (["unbreak", id], None) => journal.remove_planned_break(day, id.parse()?),
+        (["set", key, value], None) => {
+            journal.set_setting(day, &unescape_word(key), &unescape_word(value))
+        }
+        (["queue", url], Some(body)) => journal.queue_event(day, &unescape_word(url), &body),
+        (["dequeue", id], None) => journal.remove_queued_event(day, id.parse()?),
+        _ => bail!("Unknown operation."),
+    }
+}
+
+fn parse_timestamp(s: &str) -> Result<DateTime<Local>> {
+    Ok(DateTime::parse_from_rfc3339(s)?.with_timezone(&Local))
+}
+
+fn parse_optional_timestamp(s: &str) -> Result<Option<DateTime<Local>>> {
+    match s {
+        "-" => Ok(None),
+        _ => Ok(Some(parse_timestamp(s)?)),
+    }
+}
+
+/// Formats a timestamp in UTC, so that the lines written in different
+/// timezones compare and sort the same.
+fn format_timestamp(timestamp: DateTime<Local>) -> String {
+    timestamp.with_timezone(&Utc).to_rfc3339()
+}
+
+fn format_optional_timestamp(timestamp: Option<DateTime<Local>>) -> String {
+    match timestamp {
+        Some(timestamp) => format_timestamp(timestamp),
+        None => "-".to_string(),
+    }
+}
+
+/// Formats a time as HH:MM, or HH:MM:SS if it has seconds.
+fn format_time(time: NaiveTime) -> String {
+    if time.second() == 0 {
+        time.format("%H:%M").to_string()
+    } else {
+        time.format("%H:%M:%S").to_string()
+    }
+}
+
+/// Formats a duration without spaces, like "1h30m".
+fn format_duration(duration: Duration) -> Result<String> {
+    Ok(humantime::format_duration(duration.to_std()?)
+        .to_string()
+        .replace(' ', ""))
+}
+
+/// Escapes the free text at the end of a line, so that it fits in one
+/// line.
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape_text(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                unescaped.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                unescaped.push('\\');
+                chars.next();
+            }
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// Escapes a value, so that it is a single word.
+fn escape_word(word: &str) -> String {
+    word.replace('%', "%25")
+        .replace(' ', "%20")
+        .replace('\t', "%09")
+        .replace('\n', "%0A")
+}
+
+fn unescape_word(word: &str) -> String {
+    word.replace("%0A", "\n")
+        .replace("%09", "\t")
+        .replace("%20", " ")
+        .replace("%25", "%")
+}
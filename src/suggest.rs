@@ -0,0 +1,96 @@
+// Estimates learned from the finished tasks: a new task is expected to
+// take about as long as the finished tasks with a similar description
+// actually took.
+
+use crate::model::Task;
+use chrono::Duration;
+use std::collections::HashSet;
+
+/// How similar two descriptions must be for a finished task to be used
+/// in a suggestion, between 0 (no word in common) and 1 (all the words
+/// of the shorter one in the other).
+const MIN_SIMILARITY: f64 = 0.6;
+
+/// An estimate suggested from similar finished tasks.
+#[derive(Debug)]
+pub struct Suggestion {
+    /// The median of the actual durations, rounded to the minute.
+    pub estimate: Duration,
+    /// The number of similar finished tasks.
+    pub samples: usize,
+    /// The first and third quartiles of the actual durations.
+    pub spread: (Duration, Duration),
+}
+
+/// Returns the finished tasks similar to the description, with the time
+/// worked on them, the most similar first. The ones marked done without
+/// any time worked tell nothing of how long they take, and are left out.
+pub fn similar_tasks<'a>(
+    description: &str,
+    finished_tasks: &'a [(Task, Duration)],
+) -> Vec<&'a (Task, Duration)> {
+    let description_words = words(description);
+    let mut similar: Vec<(f64, &(Task, Duration))> = finished_tasks
+        .iter()
+        .filter(|(_, ellapsed)| *ellapsed > Duration::seconds(0))
+        .map(|finished_task| {
+            let task_words = words(&finished_task.0.description);
+            (similarity(&description_words, &task_words), finished_task)
+        })
+        .filter(|(score, _)| *score >= MIN_SIMILARITY)
+        .collect();
+    similar.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    similar.into_iter().map(|(_, finished_task)| finished_task).collect()
+}
+
+/// Suggests an estimate from the actual durations of the given similar
+/// tasks, if any.
+pub fn suggest(similar_tasks: &[&(Task, Duration)]) -> Option<Suggestion> {
+    let mut durations: Vec<Duration> = similar_tasks
+        .iter()
+        .map(|(_, ellapsed)| *ellapsed)
+        .collect();
+    if durations.is_empty() {
+        return None;
+    }
+    durations.sort();
+
+    let quantile = |q: usize| durations[(durations.len() - 1) * q / 4];
+    let median = quantile(2);
+    Some(Suggestion {
+        estimate: Duration::minutes(std::cmp::max(1, (median.num_seconds() + 30) / 60)),
+        samples: durations.len(),
+        spread: (quantile(1), quantile(3)),
+    })
+}
+
+/// The stems of the lowercase words of a description, ignoring
+/// punctuation.
+fn words(description: &str) -> HashSet<String> {
+    description
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| stem(&word.to_lowercase()))
+        .collect()
+}
+
+/// A word without its plural ending, so that "reviews" and "review"
+/// are the same word. Short words, and words ending in "ss" like
+/// "process", are kept.
+fn stem(word: &str) -> String {
+    match word.strip_suffix('s') {
+        Some(stem) if stem.chars().count() >= 3 && !stem.ends_with('s') => stem.to_string(),
+        _ => word.to_string(),
+    }
+}
+
+/// The share of the words of the shorter description found in the
+/// other one, so that a description completed with more details is
+/// still similar.
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let shorter = std::cmp::min(a.len(), b.len());
+    if shorter == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / shorter as f64
+}
@@ -0,0 +1,176 @@
+// Timesheets: the time worked on the finished tasks, grouped in entries
+// and rounded for billing, written as CSV.
+
+use crate::clock::Timezone;
+use crate::model::Task;
+use chrono::{DateTime, Duration, Local};
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// How durations are rounded to the increment.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rounding {
+    Nearest,
+    Up,
+}
+
+impl FromStr for Rounding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Rounding> {
+        match s {
+            "nearest" => Ok(Rounding::Nearest),
+            "up" => Ok(Rounding::Up),
+            _ => anyhow::bail!("Unknown rounding {}, expected 'nearest' or 'up'.", s),
+        }
+    }
+}
+
+/// How tasks are grouped in timesheet entries.
+#[derive(Debug, Clone, Copy)]
+pub enum Grouping {
+    /// One entry per task.
+    Task,
+    /// One entry per day.
+    Day,
+    /// One entry per day and tag.
+    Tag,
+}
+
+impl FromStr for Grouping {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Grouping> {
+        match s {
+            "task" => Ok(Grouping::Task),
+            "day" => Ok(Grouping::Day),
+            "tag" => Ok(Grouping::Tag),
+            _ => anyhow::bail!("Unknown grouping {}, expected 'task', 'day' or 'tag'.", s),
+        }
+    }
+}
+
+/// A line of the timesheet.
+#[derive(Debug)]
+pub struct Entry {
+    pub day: String,
+    pub start: DateTime<Local>,
+    pub tag: String,
+    pub description: String,
+    pub duration: Duration,
+}
+
+/// The tag of a task: the first word of its description starting with
+/// '+', like "+website".
+pub fn tag(description: &str) -> Option<&str> {
+    description
+        .split_whitespace()
+        .find(|word| word.len() > 1 && word.starts_with('+'))
+        .map(|word| &word[1..])
+}
+
+/// Groups the finished tasks, with the time worked on them, in
+/// timesheet entries, rounding the duration of each entry.
+pub fn entries(
+    finished_tasks: &[(Task, Duration)],
+    grouping: Grouping,
+    rounding: Rounding,
+    increment: Duration,
+) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+
+    for (task, ellapsed) in finished_tasks {
+        let start = match task.started_at {
+            Some(started_at) => started_at,
+            None => continue,
+        };
+        let tag = tag(&task.description).unwrap_or("").to_string();
+        let key = match grouping {
+            Grouping::Task => None,
+            Grouping::Day => Some((task.day.clone(), String::new())),
+            Grouping::Tag => Some((task.day.clone(), tag.clone())),
+        };
+
+        let existing = key.as_ref().and_then(|(day, tag)| {
+            entries
+                .iter_mut()
+                .find(|entry| entry.day == *day && entry.tag == *tag)
+        });
+        match existing {
+            Some(entry) => {
+                entry.start = std::cmp::min(entry.start, start);
+                entry.description = format!("{}; {}", entry.description, task.description);
+                entry.duration = entry.duration + *ellapsed;
+            }
+            None => entries.push(Entry {
+                day: task.day.clone(),
+                start,
+                tag: if matches!(grouping, Grouping::Day) {
+                    String::new()
+                } else {
+                    tag
+                },
+                description: task.description.clone(),
+                duration: *ellapsed,
+            }),
+        }
+    }
+
+    for entry in entries.iter_mut() {
+        entry.duration = round(entry.duration, rounding, increment);
+    }
+    entries
+}
+
+/// Rounds a duration to a multiple of the increment.
+pub fn round(duration: Duration, rounding: Rounding, increment: Duration) -> Duration {
+    let increment = increment.num_seconds();
+    if increment <= 0 {
+        return duration;
+    }
+    let seconds = duration.num_seconds();
+    let increments = match rounding {
+        Rounding::Nearest => (seconds + increment / 2) / increment,
+        Rounding::Up => (seconds + increment - 1) / increment,
+    };
+    Duration::seconds(increments * increment)
+}
+
+/// Writes the entries as CSV, with the columns expected by common time
+/// tracking tools. Start times are in the given timezone.
+pub fn write_csv(entries: &[Entry], timezone: Timezone) -> String {
+    let mut csv = String::from("Start date,Start time,Duration,Project,Description,Hours\r\n");
+    for entry in entries {
+        let seconds = entry.duration.num_seconds();
+        let fields = [
+            entry.day.clone(),
+            timezone
+                .localize(entry.start)
+                .format("%H:%M:%S")
+                .to_string(),
+            format!(
+                "{:02}:{:02}:{:02}",
+                seconds / 3600,
+                (seconds % 3600) / 60,
+                seconds % 60
+            ),
+            entry.tag.clone(),
+            entry.description.clone(),
+            format!("{:.2}", seconds as f64 / 3600.0),
+        ];
+        let fields: Vec<String> = fields.iter().map(|field| quote(field)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+/// Quotes a CSV field, if needed.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,248 @@
+// Notifications posted to the configured webhook URLs. The state of the
+// journal is compared before and after each command, and the changes
+// are queued in the journal as events:
+//
+//     start, finish   a task became active, or the active one was done
+//     pause, resume   work was stopped or started
+//     overrun         a task ran over its estimate, at the time it did
+//
+// Each event is a JSON object with the event, its time, the context
+// and the task. The queue is sent in order after each command changing
+// the journal. When a URL is unreachable or fails, its events are kept
+// and sent again after the next one, before the newer ones; the events
+// it refuses are dropped.
+
+use crate::json::task_json;
+use crate::journal::Day;
+use crate::model;
+use crate::model::{Task, TaskExtra, WorkState};
+use crate::Journal;
+use anyhow::Result;
+use chrono::{DateTime, Local, SecondsFormat};
+use serde_json::json;
+use std::collections::HashSet;
+
+/// The setting keeping the UUID of the last task reported as running
+/// over its estimate.
+const OVERRUN_SETTING: &str = "webhook-overrun";
+
+/// The number of events kept for a URL that stays unreachable. The
+/// oldest ones are dropped first, so that the journal does not keep
+/// growing.
+pub const MAX_QUEUED_EVENTS: usize = 100;
+
+#[derive(Debug, Clone, Copy)]
+enum Event {
+    Start,
+    Finish,
+    Pause,
+    Resume,
+    Overrun,
+}
+
+impl Event {
+    fn name(self) -> &'static str {
+        match self {
+            Event::Start => "start",
+            Event::Finish => "finish",
+            Event::Pause => "pause",
+            Event::Resume => "resume",
+            Event::Overrun => "overrun",
+        }
+    }
+}
+
+/// The state of the journal events are found from.
+pub struct State {
+    context: String,
+    working: bool,
+    active_task: Option<Task>,
+}
+
+/// The state of the journal, if webhooks are set.
+pub fn state(journal: &Journal) -> Result<Option<State>> {
+    if journal.config().webhooks.urls.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(State {
+        context: journal.context().to_string(),
+        working: matches!(journal.work_state()?, WorkState::Running),
+        active_task: journal.active_task()?,
+    }))
+}
+
+/// Queues the events of the changes since the 'before' state, and sends
+/// the queue. Failures are reported, but the command still succeeds.
+pub fn notify(journal: &mut Journal, before: Option<State>) {
+    let result = match before {
+        Some(before) => queue_changes(journal, &before),
+        None => Ok(()),
+    };
+    if let Err(error) = result.and_then(|_| send(journal)) {
+        eprintln!("Failed to notify webhooks: {:#}", error);
+    }
+}
+
+/// Queues the events of closing a day left running, at the time it was
+/// closed: the active task finished, and work paused.
+pub fn closed(
+    journal: &mut Journal,
+    at: DateTime<Local>,
+    finished: Option<&Task>,
+    working: bool,
+) -> Result<()> {
+    if let Some(task) = finished {
+        queue_at(journal, Event::Finish, Some(task), at)?;
+    }
+    if working {
+        queue_at(journal, Event::Pause, None, at)?;
+    }
+    Ok(())
+}
+
+/// Queues the events of the changes since the 'before' state, in the
+/// order they happened: the task that was active finished, work paused
+/// or resumed, and another task started. The tasks found running over
+/// their estimate are reported first.
+pub fn queue_changes(journal: &mut Journal, before: &State) -> Result<()> {
+    let after = match state(journal)? {
+        Some(after) if after.context == before.context => after,
+        _ => return Ok(()),
+    };
+    let today = journal.day(journal.today()?)?;
+    let active_uuid = |state: &State| state.active_task.as_ref().map(|task| task.uuid.clone());
+
+    if let Some(task) = &before.active_task {
+        if let Some(day_task) = today
+            .tasks
+            .iter()
+            .find(|day_task| day_task.task.uuid == task.uuid && day_task.task.is_done())
+        {
+            queue_overrun(journal, &today, &day_task.task)?;
+            queue(journal, Event::Finish, Some(&day_task.task))?;
+        }
+    }
+    if let Some(task) = &after.active_task {
+        queue_overrun(journal, &today, task)?;
+    }
+    if active_uuid(before) == active_uuid(&after) && before.working == after.working {
+        return Ok(());
+    }
+
+    if before.working != after.working {
+        let event = if after.working {
+            Event::Resume
+        } else {
+            Event::Pause
+        };
+        queue(journal, event, after.active_task.as_ref())?;
+    }
+    if let Some(task) = &after.active_task {
+        if active_uuid(before).as_ref() != Some(&task.uuid) {
+            queue(journal, Event::Start, Some(task))?;
+        }
+    }
+    Ok(())
+}
+
+/// Queues an overrun event the first time a task is found to have run
+/// over its estimate, dated when it did.
+fn queue_overrun(journal: &mut Journal, day: &Day, task: &Task) -> Result<()> {
+    let day_task = match day.tasks.iter().find(|day_task| day_task.task.uuid == task.uuid) {
+        Some(day_task) => day_task,
+        None => return Ok(()),
+    };
+    let started_at = match day_task.task.started_at {
+        Some(started_at) if day_task.ellapsed > day_task.task.estimated_duration => started_at,
+        _ => return Ok(()),
+    };
+    if journal.setting(OVERRUN_SETTING)?.as_ref() == Some(&task.uuid) {
+        return Ok(());
+    }
+    let at = model::worked_until(started_at, day_task.task.estimated_duration, &day.pauses);
+    queue_at(journal, Event::Overrun, Some(&day_task.task), at)?;
+    journal.set_setting(OVERRUN_SETTING, &task.uuid)?;
+    Ok(())
+}
+
+fn queue(journal: &mut Journal, event: Event, task: Option<&Task>) -> Result<()> {
+    let now = journal.now();
+    queue_at(journal, event, task, now)
+}
+
+fn queue_at(
+    journal: &mut Journal,
+    event: Event,
+    task: Option<&Task>,
+    at: DateTime<Local>,
+) -> Result<()> {
+    let body = json!({
+        "event": event.name(),
+        "at": at.to_rfc3339_opts(SecondsFormat::Secs, false),
+        "context": journal.context(),
+        "task": task.map(task_json),
+    })
+    .to_string();
+    for url in journal.config().webhooks.urls.clone() {
+        journal.queue_event(&url, &body)?;
+        drop_oldest_events(journal, &url)?;
+    }
+    Ok(())
+}
+
+/// Drops the oldest events queued for a URL beyond MAX_QUEUED_EVENTS.
+fn drop_oldest_events(journal: &mut Journal, url: &str) -> Result<()> {
+    let queued: Vec<u32> = journal
+        .queued_events()?
+        .into_iter()
+        .filter(|event| event.url == url)
+        .map(|event| event.id)
+        .collect();
+    if queued.len() <= MAX_QUEUED_EVENTS {
+        return Ok(());
+    }
+    let dropped = queued.len() - MAX_QUEUED_EVENTS;
+    for id in &queued[..dropped] {
+        journal.remove_queued_event(*id)?;
+    }
+    eprintln!("Dropped the oldest events for {}, unreachable for too long.", url);
+    Ok(())
+}
+
+/// Sends the queued events in order. After a failure, the next events
+/// to the same URL are kept, so that they arrive in order: only the
+/// URLs that cannot be reached or answer with a server error are tried
+/// again, the events refused with a client error are dropped. Events
+/// for URLs no longer configured are dropped too.
+pub fn send(journal: &mut Journal) -> Result<()> {
+    let webhooks = journal.config().webhooks.clone();
+    let agent = ureq::AgentBuilder::new()
+        .timeout(webhooks.timeout.to_std()?)
+        .build();
+    let mut unreachable = HashSet::new();
+    for event in journal.queued_events()? {
+        if !webhooks.urls.contains(&event.url) {
+            journal.remove_queued_event(event.id)?;
+            continue;
+        }
+        if unreachable.contains(&event.url) {
+            continue;
+        }
+        match agent
+            .post(&event.url)
+            .set("Content-Type", "application/json")
+            .send_string(&event.body)
+        {
+            Ok(_) => journal.remove_queued_event(event.id)?,
+            Err(ureq::Error::Status(status, _)) if status < 500 => {
+                eprintln!("Dropped an event refused by {}: status {}.", event.url, status);
+                journal.remove_queued_event(event.id)?;
+            }
+            Err(error) => {
+                eprintln!("Events will be sent again later: {}", error);
+                unreachable.insert(event.url);
+            }
+        }
+    }
+    Ok(())
+}
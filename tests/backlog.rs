@@ -0,0 +1,84 @@
+// Tasks read from the backlogs of other tools.
+
+use akiv::backlog::{read_markdown, read_taskwarrior, read_todotxt, Backlog};
+use chrono::Duration;
+
+/// The descriptions and estimates of the tasks read.
+fn items(backlog: &Backlog) -> Vec<(&str, Option<Duration>)> {
+    backlog
+        .items
+        .iter()
+        .map(|item| (item.description.as_str(), item.estimate))
+        .collect()
+}
+
+#[test]
+fn todotxt() {
+    let backlog = read_todotxt(
+        "(A) 2021-05-01 Write report est:45m +work\n\
+         x 2021-05-02 Call Bob\n\
+         \n\
+         Review budget (1h 30m)\n\
+         Plan trip est:soon\n",
+    );
+    assert_eq!(
+        items(&backlog),
+        vec![
+            ("Write report +work", Some(Duration::minutes(45))),
+            ("Review budget", Some(Duration::minutes(90))),
+        ]
+    );
+    assert_eq!(backlog.done, 1);
+    assert_eq!(backlog.problems, vec!["line 5: invalid estimate soon."]);
+}
+
+#[test]
+fn markdown_checklists() {
+    let backlog = read_markdown(
+        "# Today\n\
+         \n\
+         - [ ] Write report (30m)\n\
+         - [x] Call Bob\n\
+         * [ ] Review budget\n\
+         - [akiv](https://github.com/sgarciac/akiv)\n\
+         Some notes.\n",
+    );
+    assert_eq!(
+        items(&backlog),
+        vec![
+            ("Write report", Some(Duration::minutes(30))),
+            ("Review budget", None),
+        ]
+    );
+    assert_eq!(backlog.done, 1);
+    assert!(backlog.problems.is_empty());
+}
+
+#[test]
+fn taskwarrior_exports() {
+    let export = r#"[
+        {"uuid": "5e2a", "description": "Write report", "status": "pending", "estimate": "PT45M"},
+        {"uuid": "8c1d", "description": "Call Bob", "status": "completed"},
+        {"uuid": "9f3b", "description": "Review budget", "status": "pending", "estimate": 20},
+        {"uuid": "a7e0", "status": "pending"}
+    ]"#;
+    let backlog = read_taskwarrior(export);
+    assert_eq!(
+        items(&backlog),
+        vec![
+            ("Write report", Some(Duration::minutes(45))),
+            ("Review budget", Some(Duration::minutes(20))),
+        ]
+    );
+    assert_eq!(backlog.items[0].uid.as_deref(), Some("5e2a@taskwarrior"));
+    assert_eq!(backlog.done, 1);
+    assert_eq!(backlog.problems, vec!["task 4: no description."]);
+
+    // One task per line, without the array.
+    let lines: Vec<&str> = export
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with('{'))
+        .collect();
+    assert_eq!(items(&read_taskwarrior(&lines.join("\n"))), items(&backlog));
+}
@@ -0,0 +1,112 @@
+// Helpers shared by the tests. Each test crate uses some of them.
+#![allow(dead_code)]
+
+use akiv::clock::{FixedClock, Timezone, NOW_VARIABLE};
+use akiv::config::Config;
+use akiv::model::Task;
+use akiv::Journal;
+use chrono::{DateTime, Duration, Local, NaiveDate};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::rc::Rc;
+
+/// A time given in RFC 3339.
+pub fn time(s: &str) -> DateTime<Local> {
+    DateTime::parse_from_rfc3339(s)
+        .unwrap()
+        .with_timezone(&Local)
+}
+
+/// The day the scenarios of the tests happen on.
+pub fn day() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2021, 5, 3).unwrap()
+}
+
+/// The timezone of the scenarios.
+pub fn paris() -> Timezone {
+    "Europe/Paris".parse().unwrap()
+}
+
+/// A journal in memory counting days in Paris, whatever the timezone
+/// of the system running the tests.
+pub fn journal() -> Journal {
+    journal_with(Config::default())
+}
+
+/// A journal in memory with the given settings, counting days in Paris.
+pub fn journal_with(config: Config) -> Journal {
+    let config = Config {
+        timezone: paris(),
+        ..config
+    };
+    Journal::open_in_memory(config).unwrap()
+}
+
+/// Stops the clock of the journal at the given time of the day of the
+/// scenarios, in Paris.
+pub fn at(journal: &mut Journal, hour_minute: &str) {
+    let now = time(&format!("2021-05-03T{}:00+02:00", hour_minute));
+    journal.set_clock(Rc::new(FixedClock(now)));
+}
+
+/// A pending task of the day of the scenarios, at the given position.
+pub fn task(position: u32, description: &str, estimate: Duration) -> Task {
+    Task {
+        id: position,
+        uuid: uuid::Uuid::new_v4().to_string(),
+        description: description.to_string(),
+        created_at: time("2021-05-03T08:00:00+02:00"),
+        started_at: None,
+        finished_at: None,
+        context: "default".to_string(),
+        day: "2021-05-03".to_string(),
+        position,
+        estimated_duration: estimate,
+        fixed_at: None,
+        note: None,
+        source_uid: None,
+        git: None,
+        pomodoros: 0,
+    }
+}
+
+/// A directory removed with everything in it when dropped, even if the
+/// test fails.
+pub struct TempDir(PathBuf);
+
+/// A new directory for the journals of a test.
+pub fn temp_dir() -> TempDir {
+    let dir = std::env::temp_dir().join(format!("akiv-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    TempDir(dir)
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Runs akiv on the text journal "journal.txt" of the given directory,
+/// with the settings of "config.toml" if any, at 09:00 in Paris on the
+/// day of the scenarios.
+pub fn akiv(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_akiv"))
+        .arg("--journal-file")
+        .arg(dir.join("journal.txt"))
+        .arg("--config-file")
+        .arg(dir.join("config.toml"))
+        .args(args)
+        .env(NOW_VARIABLE, "2021-05-03T09:00:00+02:00")
+        .output()
+        .unwrap()
+}
@@ -0,0 +1,135 @@
+// Contexts: separate lists of tasks in the same journal, each with its
+// own positions and work state.
+
+mod common;
+
+use akiv::config::Config;
+use akiv::model::{TaskExtra, WorkState};
+use akiv::storage::Backend;
+use akiv::Journal;
+use chrono::{Duration, NaiveTime};
+use common::{akiv, at, day, journal, temp_dir, time};
+use std::path::Path;
+
+fn descriptions(journal: &Journal) -> Vec<String> {
+    journal
+        .tasks(day())
+        .unwrap()
+        .into_iter()
+        .map(|task| task.description)
+        .collect()
+}
+
+#[test]
+fn contexts_have_their_own_tasks_and_work() {
+    let mut journal = journal();
+    at(&mut journal, "09:00");
+    journal
+        .add("Write report", Duration::hours(1), None, None)
+        .unwrap();
+    journal
+        .add("Call", Duration::minutes(15), None, None)
+        .unwrap();
+    journal.start().unwrap();
+
+    journal.set_context("personal").unwrap();
+    assert!(descriptions(&journal).is_empty());
+    assert!(journal.active_task().unwrap().is_none());
+    assert!(matches!(journal.work_state().unwrap(), WorkState::Stopped));
+    let task = journal
+        .add("Groceries", Duration::minutes(30), None, None)
+        .unwrap();
+    assert_eq!(task.position, 1);
+    at(&mut journal, "09:10");
+    journal.start().unwrap();
+    at(&mut journal, "09:20");
+    journal.stop().unwrap();
+
+    journal.set_context("default").unwrap();
+    assert_eq!(descriptions(&journal), vec!["Write report", "Call"]);
+    assert!(matches!(journal.work_state().unwrap(), WorkState::Running));
+    assert!(journal.pauses(day()).unwrap().is_empty());
+    at(&mut journal, "09:30");
+    let next = journal.next().unwrap();
+    assert_eq!(next.finished.unwrap().description, "Write report");
+    assert_eq!(next.started.unwrap().description, "Call");
+
+    journal.set_context("personal").unwrap();
+    assert_eq!(descriptions(&journal), vec!["Groceries"]);
+    let groceries = journal.active_task().unwrap().unwrap();
+    assert_eq!(groceries.description, "Groceries");
+    assert!(!groceries.is_done());
+    assert!(matches!(journal.work_state().unwrap(), WorkState::Stopped));
+    assert_eq!(
+        journal.pauses(day()).unwrap(),
+        vec![(time("2021-05-03T09:20:00+02:00"), None)]
+    );
+    assert!(matches!(journal.next(), Err(akiv::Error::WorkStopped)));
+
+    assert_eq!(journal.contexts().unwrap(), vec!["default", "personal"]);
+}
+
+#[test]
+fn planned_breaks_are_shared_by_the_contexts() {
+    let mut journal = journal();
+    at(&mut journal, "09:00");
+    journal
+        .add_break(NaiveTime::from_hms_opt(12, 0, 0).unwrap(), Duration::hours(1))
+        .unwrap();
+    journal.set_context("personal").unwrap();
+    assert_eq!(journal.planned_breaks().unwrap().len(), 1);
+}
+
+#[test]
+fn contexts_are_single_words() {
+    let mut journal = journal();
+    assert!(journal.set_context("").is_err());
+    assert!(journal.set_context("side project").is_err());
+    assert_eq!(journal.context(), "default");
+}
+
+#[test]
+fn the_context_used_is_kept_until_another_one_is_used() {
+    let dir = temp_dir();
+    let path = dir.join("journal.txt");
+    let open = || Journal::open(&path, Some(Backend::Text), Config::default()).unwrap();
+
+    let mut journal = open();
+    assert_eq!(journal.context(), "default");
+    journal.use_context("personal").unwrap();
+
+    // Setting the context only applies to the journal opened, like
+    // '--context' only applies to one command.
+    let mut journal = open();
+    assert_eq!(journal.context(), "personal");
+    journal.set_context("work").unwrap();
+    assert_eq!(open().context(), "personal");
+}
+
+/// Runs akiv, checks that it succeeds, and returns its output.
+fn output(dir: &Path, args: &[&str]) -> String {
+    let output = akiv(dir, args);
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn the_context_can_be_given_for_one_command() {
+    let dir = temp_dir();
+    output(&dir, &["add", "Write report", "1h"]);
+    output(&dir, &["--context", "personal", "add", "Groceries", "30m"]);
+
+    let default = output(&dir, &["list"]);
+    assert!(default.contains("Write report"));
+    assert!(!default.contains("Groceries"));
+    let personal = output(&dir, &["--context", "personal", "list"]);
+    assert!(personal.contains("Groceries"));
+    assert!(!personal.contains("Write report"));
+
+    let all = output(&dir, &["list", "--all-contexts"]);
+    let personal_start = all.find("personal:").unwrap();
+    assert!(all.starts_with("default:"));
+    assert!(all[..personal_start].contains("Write report"));
+    assert!(all[personal_start..].contains("Groceries"));
+    assert!(!all[personal_start..].contains("Write report"));
+}
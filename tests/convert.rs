@@ -0,0 +1,136 @@
+// Converting journals between backends keeps everything in them.
+
+mod common;
+
+use akiv::clock::FixedClock;
+use akiv::config::Config;
+use akiv::model::Task;
+use akiv::storage::{self, Backend, Storage};
+use akiv::Journal;
+use chrono::{Duration, NaiveTime};
+use std::rc::Rc;
+use common::{day, temp_dir, time};
+
+/// The fields of a task kept by a conversion: all but the id.
+fn kept(task: &Task) -> String {
+    format!(
+        "{} {} {} {} {:?} {:?} {:?} {} {:?} {:?} {:?} {:?} {}",
+        task.uuid,
+        task.day,
+        task.position,
+        task.description,
+        task.created_at,
+        task.started_at,
+        task.finished_at,
+        task.estimated_duration,
+        task.fixed_at,
+        task.note,
+        task.source_uid,
+        task.git,
+        task.pomodoros,
+    )
+}
+
+/// Everything in a journal, as text to compare.
+fn dump(storage: &dyn Storage) -> Vec<String> {
+    let mut lines = Vec::new();
+    for day in storage.days().unwrap() {
+        for context in storage.contexts().unwrap() {
+            for task in storage.tasks(&context, day).unwrap() {
+                lines.push(format!("{} task {}", context, kept(&task)));
+            }
+            for at in storage.work_events(&context, day).unwrap() {
+                lines.push(format!("{} {} work {:?}", context, day, at));
+            }
+        }
+        lines.push(format!(
+            "{} end of work {:?}",
+            day,
+            storage.end_of_work(day).unwrap()
+        ));
+        for planned_break in storage.planned_breaks(day).unwrap() {
+            lines.push(format!(
+                "{} break {} {}",
+                day, planned_break.start, planned_break.duration
+            ));
+        }
+    }
+    for (key, value) in storage.settings().unwrap() {
+        lines.push(format!("setting {} {}", key, value));
+    }
+    for event in storage.queued_events().unwrap() {
+        lines.push(format!("event {} {}", event.url, event.body));
+    }
+    lines
+}
+
+#[test]
+fn sqlite_to_text_to_sqlite() {
+    let dir = temp_dir();
+    let sqlite = dir.join("journal.sqlite");
+    let text = dir.join("journal.txt");
+    let copy = dir.join("copy.sqlite");
+
+    {
+        let mut journal = Journal::open(&sqlite, Some(Backend::Sqlite), Config::default()).unwrap();
+        journal.set_clock(Rc::new(FixedClock(time("2021-05-03T09:00:00+02:00"))));
+        journal
+            .add("Write report", Duration::minutes(30), None, None)
+            .unwrap();
+        journal
+            .add(
+                "Call",
+                Duration::minutes(15),
+                None,
+                NaiveTime::from_hms_opt(14, 0, 0),
+            )
+            .unwrap();
+        journal
+            .add("Review", Duration::hours(1), None, None)
+            .unwrap();
+        journal.start().unwrap();
+        journal.note(1, "Sent the draft").unwrap();
+        journal
+            .add_break(
+                NaiveTime::from_hms_opt(12, 30, 0).unwrap(),
+                Duration::minutes(45),
+            )
+            .unwrap();
+        journal
+            .set_end_of_work(NaiveTime::from_hms_opt(18, 0, 0).unwrap())
+            .unwrap();
+
+        journal.set_clock(Rc::new(FixedClock(time("2021-05-03T09:40:00+02:00"))));
+        journal.next().unwrap();
+        journal.set_clock(Rc::new(FixedClock(time("2021-05-03T10:00:00+02:00"))));
+        journal.stop().unwrap();
+        journal.add_pomodoro(3).unwrap();
+
+        journal.set_context("personal").unwrap();
+        journal
+            .add("Groceries", Duration::minutes(20), None, None)
+            .unwrap();
+        journal.set_setting("webhook-overrun", "some-uuid").unwrap();
+        journal
+            .queue_event("http://localhost/hook", "{\"event\":\"start\"}")
+            .unwrap();
+    }
+
+    let original = storage::open(&sqlite, None).unwrap();
+    let mut as_text = storage::open(&text, None).unwrap();
+    assert_eq!(
+        storage::copy(original.as_ref(), as_text.as_mut(), day()).unwrap(),
+        1
+    );
+    drop(as_text);
+
+    let as_text = storage::open(&text, None).unwrap();
+    let mut back = storage::open(&copy, None).unwrap();
+    storage::copy(as_text.as_ref(), back.as_mut(), day()).unwrap();
+
+    let expected = dump(original.as_ref());
+    assert!(expected.iter().any(|line| line.contains("Groceries")));
+    assert_eq!(dump(as_text.as_ref()), expected);
+    assert_eq!(dump(back.as_ref()), expected);
+    assert_eq!(back.tasks("default", day()).unwrap().len(), 3);
+}
@@ -0,0 +1,52 @@
+// Forecasts of the end of the day, from how long the finished tasks
+// took compared to their estimates.
+
+mod common;
+
+use akiv::forecast::{forecast, ratios};
+use chrono::Duration;
+use common::{paris, task, time};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+#[test]
+fn tasks_done_without_time_worked_are_not_history() {
+    let finished_tasks = vec![
+        (task(1, "Write report", Duration::hours(1)), Duration::minutes(90)),
+        (task(2, "Call", Duration::minutes(30)), Duration::minutes(15)),
+        (task(3, "Email", Duration::minutes(10)), Duration::zero()),
+    ];
+    assert_eq!(ratios(&finished_tasks), vec![1.5, 0.5]);
+}
+
+#[test]
+fn percentiles_follow_the_history() {
+    // One task in ten took twice its estimate, the others as long as
+    // expected.
+    let mut ratios = vec![1.0; 9];
+    ratios.push(2.0);
+    let tasks = vec![
+        task(1, "Write report", Duration::hours(1)),
+        task(2, "Call", Duration::minutes(30)),
+    ];
+    let timezone = paris();
+    let now = time("2021-05-03T09:00:00+02:00");
+
+    let forecast = forecast(
+        &tasks,
+        &[Duration::zero(), Duration::zero()],
+        &[],
+        &ratios,
+        now,
+        timezone,
+        &mut StdRng::seed_from_u64(1),
+    );
+
+    let [p50, p80, p95] = forecast.tasks[0].unwrap();
+    assert_eq!(p50, time("2021-05-03T10:00:00+02:00"));
+    assert_eq!(p80, time("2021-05-03T10:00:00+02:00"));
+    assert_eq!(p95, time("2021-05-03T11:00:00+02:00"));
+    let [p50, _, p95] = forecast.day.unwrap();
+    assert_eq!(p50, time("2021-05-03T10:30:00+02:00"));
+    assert_eq!(p95, time("2021-05-03T11:30:00+02:00"));
+}
@@ -0,0 +1,110 @@
+// Hooks: shell commands run after the changes, given the event and the
+// task.
+
+mod common;
+
+use akiv::clock::FixedClock;
+use akiv::config::{Config, HooksConfig};
+use akiv::hooks::{self, Event};
+use akiv::model::WorkState;
+use akiv::storage::Backend;
+use akiv::Journal;
+use chrono::Duration;
+use common::{akiv, at, journal_with, temp_dir, time};
+use serde_json::Value;
+use std::rc::Rc;
+use std::time::Instant;
+
+#[test]
+fn hooks_are_given_the_event_and_the_tasks() {
+    let dir = temp_dir();
+    let env = dir.join("env");
+    let input = dir.join("input.json");
+    let config = Config {
+        hooks: HooksConfig {
+            next: Some(format!(
+                "env > '{}'; cat > '{}'",
+                env.display(),
+                input.display()
+            )),
+            ..HooksConfig::default()
+        },
+        ..Config::default()
+    };
+    let mut journal = journal_with(config);
+    at(&mut journal, "09:00");
+    journal
+        .add("Write report", Duration::hours(1), None, None)
+        .unwrap();
+    journal
+        .add("Call", Duration::minutes(15), None, None)
+        .unwrap();
+    journal.start().unwrap();
+    at(&mut journal, "09:30");
+    let next = journal.next().unwrap();
+    hooks::run(
+        &journal,
+        Event::Next,
+        next.started.as_ref(),
+        next.finished.as_ref(),
+    );
+
+    let env = std::fs::read_to_string(env).unwrap();
+    let started = next.started.unwrap();
+    for variable in [
+        "AKIV_EVENT=next".to_string(),
+        "AKIV_CONTEXT=default".to_string(),
+        "AKIV_WORK_STATE=running".to_string(),
+        "AKIV_TASK_POSITION=2".to_string(),
+        "AKIV_TASK_DESCRIPTION=Call".to_string(),
+        format!("AKIV_TASK_UUID={}", started.uuid),
+    ] {
+        assert!(env.lines().any(|line| line == variable), "{} not in {}", variable, env);
+    }
+
+    let input: Value = serde_json::from_str(&std::fs::read_to_string(input).unwrap()).unwrap();
+    assert_eq!(input["event"], "next");
+    assert_eq!(input["context"], "default");
+    assert_eq!(input["work_state"], "running");
+    assert_eq!(input["task"]["description"], "Call");
+    assert_eq!(input["task"]["state"], "active");
+    assert_eq!(input["finished"]["description"], "Write report");
+    assert_eq!(input["finished"]["state"], "done");
+    assert_eq!(input["finished"]["uuid"], next.finished.unwrap().uuid.as_str());
+}
+
+#[test]
+fn hooks_running_too_long_are_killed() {
+    let config = Config {
+        hooks: HooksConfig {
+            start: Some("sleep 10".to_string()),
+            timeout: Duration::milliseconds(200),
+            ..HooksConfig::default()
+        },
+        ..Config::default()
+    };
+    let mut journal = journal_with(config);
+    at(&mut journal, "09:00");
+
+    let started = Instant::now();
+    hooks::run(&journal, Event::Start, None, None);
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+}
+
+#[test]
+fn failing_hooks_do_not_fail_the_command() {
+    let dir = temp_dir();
+    std::fs::write(dir.join("config.toml"), "[hooks]\nstart = \"exit 3\"\n").unwrap();
+    assert!(akiv(&dir, &["add", "Write report", "1h"]).status.success());
+
+    let output = akiv(&dir, &["start"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Hook 'start' failed"), "{}", stderr);
+
+    // The change was saved all the same.
+    let mut journal =
+        Journal::open(&dir.join("journal.txt"), Some(Backend::Text), Config::default()).unwrap();
+    journal.set_clock(Rc::new(FixedClock(time("2021-05-03T09:00:00+02:00"))));
+    assert!(matches!(journal.work_state().unwrap(), WorkState::Running));
+}
@@ -0,0 +1,165 @@
+// Day plans exported as iCalendar files, and appointments read from
+// them.
+
+mod common;
+
+use akiv::clock::FixedClock;
+use akiv::config::Config;
+use akiv::ics::{read_events, write_calendar, CalendarEvent, Event};
+use akiv::Journal;
+use chrono::Duration;
+use common::{day, paris, time};
+use std::rc::Rc;
+
+/// A calendar with the given events.
+fn calendar(events: &[&str]) -> String {
+    let mut calendar = String::from("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n");
+    for event in events {
+        calendar.push_str("BEGIN:VEVENT\r\n");
+        calendar.push_str(&event.replace('\n', "\r\n"));
+        calendar.push_str("\r\nEND:VEVENT\r\n");
+    }
+    calendar.push_str("END:VCALENDAR\r\n");
+    calendar
+}
+
+/// The events of the day of the scenarios, in Paris.
+fn events(calendar: &str) -> Vec<CalendarEvent> {
+    let (events, problems) = read_events(calendar, day(), paris());
+    assert!(problems.is_empty(), "{:?}", problems);
+    events
+}
+
+/// A weekly standup on Mondays at 10:00 in Paris, since 2021-04-19,
+/// with the given extra properties.
+fn standup(properties: &str) -> String {
+    format!(
+        "UID:standup@example.com\n\
+         SUMMARY:Standup\n\
+         DTSTART;TZID=Europe/Paris:20210419T100000\n\
+         DTEND;TZID=Europe/Paris:20210419T101500\n\
+         {}",
+        properties
+    )
+}
+
+#[test]
+fn weekly_events_last_until_their_end() {
+    let until = calendar(&[&standup("RRULE:FREQ=WEEKLY;UNTIL=20210510T080000Z")]);
+    let occurrences = events(&until);
+    assert_eq!(occurrences.len(), 1);
+    assert_eq!(occurrences[0].summary, "Standup");
+    assert_eq!(occurrences[0].start, time("2021-05-03T10:00:00+02:00"));
+    assert_eq!(occurrences[0].end, time("2021-05-03T10:15:00+02:00"));
+
+    let ended = calendar(&[&standup("RRULE:FREQ=WEEKLY;UNTIL=20210426T235959Z")]);
+    assert!(events(&ended).is_empty());
+}
+
+#[test]
+fn excluded_occurrences_are_left_out() {
+    let calendar = calendar(&[&standup(
+        "RRULE:FREQ=WEEKLY\n\
+         EXDATE;TZID=Europe/Paris:20210426T100000,20210503T100000",
+    )]);
+    assert!(events(&calendar).is_empty());
+}
+
+#[test]
+fn modified_occurrences_replace_the_regular_ones() {
+    let calendar = calendar(&[
+        &standup("RRULE:FREQ=WEEKLY"),
+        "UID:standup@example.com\n\
+         SUMMARY:Standup (moved)\n\
+         RECURRENCE-ID;TZID=Europe/Paris:20210503T100000\n\
+         DTSTART;TZID=Europe/Paris:20210503T113000\n\
+         DURATION:PT15M",
+    ]);
+    let events = events(&calendar);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].summary, "Standup (moved)");
+    assert_eq!(events[0].start, time("2021-05-03T11:30:00+02:00"));
+    assert_eq!(events[0].end, time("2021-05-03T11:45:00+02:00"));
+}
+
+#[test]
+fn times_are_converted_from_the_time_zone_of_the_event() {
+    let calendar = calendar(&[
+        "UID:call@example.com\n\
+         SUMMARY:Call with New York\n\
+         DTSTART;TZID=America/New_York:20210503T090000\n\
+         DTEND;TZID=America/New_York:20210503T093000",
+        "UID:lunch@example.com\n\
+         SUMMARY:Lunch\n\
+         DTSTART:20210503T103000Z\n\
+         DURATION:PT1H",
+    ]);
+    let events = events(&calendar);
+    let starts: Vec<_> = events.iter().map(|event| event.start).collect();
+    assert_eq!(
+        starts,
+        vec![
+            time("2021-05-03T12:30:00+02:00"),
+            time("2021-05-03T15:00:00+02:00"),
+        ]
+    );
+    assert_eq!(events[1].end - events[1].start, Duration::minutes(30));
+}
+
+#[test]
+fn events_already_imported_are_skipped() {
+    let config = Config {
+        timezone: paris(),
+        ..Config::default()
+    };
+    let mut journal = Journal::open_in_memory(config).unwrap();
+    journal.set_clock(Rc::new(FixedClock(time("2021-05-03T08:00:00+02:00"))));
+    let calendar = calendar(&[&standup("RRULE:FREQ=WEEKLY")]);
+
+    for imported in [true, false] {
+        for event in events(&calendar) {
+            let fixed_at = paris().localize(event.start).time();
+            let task = journal
+                .import(
+                    Some(&event.uid),
+                    &event.summary,
+                    event.end - event.start,
+                    Some(fixed_at),
+                )
+                .unwrap();
+            assert_eq!(task.is_some(), imported);
+        }
+    }
+    assert_eq!(journal.tasks(day()).unwrap().len(), 1);
+}
+
+#[test]
+fn exported_events_are_read_back() {
+    let summary = "Réunion équipe; budget, planning \\ suivi\nété 2021, café à volonté, \
+                   et pâtisseries ☕ pour tout le monde";
+    let exported = Event {
+        uid: "123e4567-e89b-12d3-a456-426614174000".to_string(),
+        summary: summary.to_string(),
+        description: String::new(),
+        start: time("2021-05-03T10:00:00+02:00"),
+        end: time("2021-05-03T10:45:00+02:00"),
+        tentative: false,
+    };
+    let calendar = write_calendar(&[exported], time("2021-05-03T08:00:00+02:00"));
+
+    // The summary is folded before "à", which would end at octet 76.
+    assert!(calendar.contains("café \r\n à volonté"));
+    for line in calendar.split("\r\n") {
+        assert!(line.len() <= 75, "{:?} is longer than 75 octets", line);
+    }
+    assert!(calendar.contains("\r\nDTSTART:20210503T080000Z\r\n"));
+    assert!(calendar.contains("\r\nDTEND:20210503T084500Z\r\n"));
+    assert!(calendar.contains("SUMMARY:Réunion équipe\\; budget\\, planning \\\\ suivi\\n"));
+
+    let events = events(&calendar);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].uid, "123e4567-e89b-12d3-a456-426614174000");
+    assert_eq!(events[0].summary, summary);
+    assert_eq!(events[0].start, time("2021-05-03T10:00:00+02:00"));
+    assert_eq!(events[0].end, time("2021-05-03T10:45:00+02:00"));
+}
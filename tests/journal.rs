@@ -0,0 +1,293 @@
+// Days of work replayed on a journal in memory, with the clock stopped
+// at the times of each step.
+
+mod common;
+
+use akiv::clock::{self, FixedClock};
+use akiv::config::{CloseAt, CloseTask};
+use akiv::model::{TaskExtra, WorkState};
+use akiv::Error;
+use chrono::{Duration, NaiveDate, NaiveTime};
+use std::rc::Rc;
+use common::{at, day, journal, time};
+
+#[test]
+fn start_stop_and_next() {
+    let mut journal = journal();
+    at(&mut journal, "09:00");
+    journal
+        .add("Write report", Duration::hours(1), None, None)
+        .unwrap();
+    journal
+        .add("Call", Duration::minutes(15), None, None)
+        .unwrap();
+
+    let started = journal.start().unwrap().unwrap();
+    assert_eq!(started.description, "Write report");
+    assert!(matches!(journal.start(), Err(Error::AlreadyWorking)));
+
+    at(&mut journal, "09:20");
+    journal.stop().unwrap();
+    assert!(matches!(journal.work_state().unwrap(), WorkState::Stopped));
+    assert!(matches!(journal.next(), Err(Error::WorkStopped)));
+
+    at(&mut journal, "09:30");
+    journal.start().unwrap();
+    at(&mut journal, "10:00");
+    let next = journal.next().unwrap();
+    assert_eq!(next.finished.unwrap().description, "Write report");
+    assert_eq!(next.started.unwrap().started_at, Some(journal.now()));
+
+    let tasks = journal.day(day()).unwrap().tasks;
+    assert_eq!(tasks[0].ellapsed, Duration::minutes(50));
+    assert_eq!(tasks[0].paused, Duration::minutes(10));
+    assert!(tasks[1].task.is_active());
+}
+
+#[test]
+fn work_stops_after_the_last_task() {
+    let mut journal = journal();
+    at(&mut journal, "09:00");
+    journal
+        .add("Call", Duration::minutes(15), None, None)
+        .unwrap();
+    journal.start().unwrap();
+
+    at(&mut journal, "09:15");
+    let next = journal.next().unwrap();
+    assert!(next.started.is_none());
+    assert!(matches!(journal.work_state().unwrap(), WorkState::Stopped));
+    assert!(matches!(journal.start(), Err(Error::NoTasks)));
+}
+
+#[test]
+fn reopening_keeps_the_time_worked() {
+    let mut journal = journal();
+    at(&mut journal, "09:00");
+    journal
+        .add("Write report", Duration::hours(1), None, None)
+        .unwrap();
+    journal
+        .add("Call", Duration::minutes(15), None, None)
+        .unwrap();
+    journal.start().unwrap();
+    at(&mut journal, "09:30");
+    journal.next().unwrap();
+    at(&mut journal, "09:40");
+    journal.stop().unwrap();
+    at(&mut journal, "09:50");
+    journal.done(2, None).unwrap();
+
+    at(&mut journal, "10:00");
+    let task = journal.reopen(1).unwrap();
+    assert!(task.is_active());
+    assert!(matches!(journal.reopen(1), Err(Error::NotDone(1))));
+    journal.start().unwrap();
+
+    at(&mut journal, "10:10");
+    let tasks = journal.day(day()).unwrap().tasks;
+    assert_eq!(tasks[0].ellapsed, Duration::minutes(40));
+}
+
+#[test]
+fn tasks_done_without_starting_them_skip_the_pauses() {
+    let mut journal = journal();
+    at(&mut journal, "09:00");
+    journal
+        .add("Write report", Duration::hours(1), None, None)
+        .unwrap();
+    journal
+        .add("Call", Duration::minutes(15), None, None)
+        .unwrap();
+    journal.start().unwrap();
+    at(&mut journal, "09:30");
+    journal.stop().unwrap();
+    at(&mut journal, "09:50");
+    journal.start().unwrap();
+
+    at(&mut journal, "10:00");
+    let task = journal.done(2, Some(Duration::minutes(30))).unwrap();
+    assert_eq!(task.started_at, Some(time("2021-05-03T09:10:00+02:00")));
+    let tasks = journal.day(day()).unwrap().tasks;
+    assert_eq!(tasks[1].ellapsed, Duration::minutes(30));
+}
+
+#[test]
+fn only_tasks_not_started_can_be_moved() {
+    let mut journal = journal();
+    at(&mut journal, "09:00");
+    for description in ["Write report", "Call", "Review", "Lunch"] {
+        journal
+            .add(description, Duration::minutes(30), None, None)
+            .unwrap();
+    }
+    journal.start().unwrap();
+    at(&mut journal, "09:30");
+    journal.next().unwrap();
+
+    assert!(matches!(journal.move_task(1, 4), Err(Error::MoveStarted(1))));
+    assert!(matches!(journal.move_task(2, 4), Err(Error::MoveStarted(2))));
+    assert!(matches!(journal.move_task(5, 1), Err(Error::NoSuchTask(5))));
+
+    let moved = journal.move_task(4, 1).unwrap();
+    assert_eq!(moved.description, "Lunch");
+    assert_eq!(moved.position, 1);
+    let moved = journal.move_task(1, 10).unwrap();
+    assert_eq!(moved.position, 4);
+    let descriptions: Vec<String> = journal
+        .tasks(day())
+        .unwrap()
+        .into_iter()
+        .map(|task| task.description)
+        .collect();
+    assert_eq!(descriptions, vec!["Write report", "Call", "Review", "Lunch"]);
+}
+
+#[test]
+fn appointments_are_not_started_before_their_time() {
+    let mut journal = journal();
+    at(&mut journal, "09:00");
+    journal
+        .add(
+            "Meeting",
+            Duration::minutes(30),
+            None,
+            NaiveTime::from_hms_opt(14, 0, 0),
+        )
+        .unwrap();
+    journal
+        .add("Write report", Duration::hours(1), None, None)
+        .unwrap();
+
+    let started = journal.start().unwrap().unwrap();
+    assert_eq!(started.description, "Write report");
+
+    at(&mut journal, "10:00");
+    let next = journal.next().unwrap();
+    assert!(next.started.is_none());
+    assert!(matches!(journal.work_state().unwrap(), WorkState::Running));
+
+    at(&mut journal, "14:00");
+    journal.stop().unwrap();
+    let next = journal.next().unwrap();
+    assert_eq!(next.started.unwrap().description, "Meeting");
+}
+
+#[test]
+fn days_left_running_are_closed() {
+    let mut journal = journal();
+    at(&mut journal, "17:00");
+    journal
+        .add("Write report", Duration::hours(1), None, None)
+        .unwrap();
+    journal.start().unwrap();
+
+    journal.set_clock(Rc::new(FixedClock(time("2021-05-04T09:00:00+02:00"))));
+    let unclosed_days = journal.unclosed_days().unwrap();
+    assert_eq!(unclosed_days.len(), 1);
+    let unclosed_day = &unclosed_days[0];
+    assert_eq!(unclosed_day.day, day());
+    assert!(unclosed_day.working);
+    assert_eq!(
+        unclosed_day.last_activity,
+        time("2021-05-03T17:00:00+02:00")
+    );
+
+    assert!(matches!(
+        journal.close_day(
+            unclosed_day,
+            time("2021-05-03T16:00:00+02:00"),
+            CloseTask::CarryOver
+        ),
+        Err(Error::CloseBeforeLastActivity)
+    ));
+    let carried_over = journal
+        .close_day(
+            unclosed_day,
+            time("2021-05-03T17:40:00+02:00"),
+            CloseTask::CarryOver,
+        )
+        .unwrap()
+        .carried_over
+        .unwrap();
+    assert_eq!(carried_over.description, "Write report");
+    assert_eq!(carried_over.estimated_duration, Duration::minutes(20));
+    journal.mark_days_closed().unwrap();
+
+    assert!(journal.unclosed_days().unwrap().is_empty());
+    let yesterday = journal.day(day()).unwrap().tasks;
+    assert!(yesterday[0].task.is_done());
+    assert_eq!(yesterday[0].ellapsed, Duration::minutes(40));
+    assert!(matches!(journal.work_state().unwrap(), WorkState::Stopped));
+}
+
+#[test]
+fn days_left_working_are_not_closed_at_their_last_activity() {
+    let mut journal = journal();
+    at(&mut journal, "09:00");
+    journal
+        .add("Write report", Duration::hours(2), None, None)
+        .unwrap();
+    journal.start().unwrap();
+
+    // Work was started, and never stopped: an odd number of work
+    // events, and no sign of when the day ended.
+    journal.set_clock(Rc::new(FixedClock(time("2021-05-04T09:00:00+02:00"))));
+    let unclosed_day = &journal.unclosed_days().unwrap()[0];
+    assert!(unclosed_day.working);
+    assert_eq!(unclosed_day.last_activity, time("2021-05-03T09:00:00+02:00"));
+    assert_eq!(unclosed_day.close_time(CloseAt::LastActivity), None);
+    assert_eq!(unclosed_day.close_time(CloseAt::EndOfWork), None);
+
+    // The end of work of the day tells.
+    at(&mut journal, "09:00");
+    journal
+        .set_end_of_work(NaiveTime::from_hms_opt(18, 0, 0).unwrap())
+        .unwrap();
+    journal.set_clock(Rc::new(FixedClock(time("2021-05-04T09:00:00+02:00"))));
+    let unclosed_day = &journal.unclosed_days().unwrap()[0];
+    let close_time = unclosed_day.close_time(CloseAt::LastActivity).unwrap();
+    assert_eq!(close_time, time("2021-05-03T18:00:00+02:00"));
+    let closed = journal
+        .close_day(unclosed_day, close_time, CloseTask::Finish)
+        .unwrap();
+    assert_eq!(closed.finished.unwrap().finished_at, Some(close_time));
+    assert!(closed.carried_over.is_none());
+    let yesterday = journal.day(day()).unwrap().tasks;
+    assert_eq!(yesterday[0].ellapsed, Duration::hours(9));
+}
+
+#[test]
+fn days_with_work_stopped_are_closed_at_their_last_activity() {
+    let mut journal = journal();
+    at(&mut journal, "09:00");
+    journal
+        .add("Write report", Duration::hours(2), None, None)
+        .unwrap();
+    journal.start().unwrap();
+    at(&mut journal, "10:30");
+    journal.stop().unwrap();
+
+    journal.set_clock(Rc::new(FixedClock(time("2021-05-04T09:00:00+02:00"))));
+    let unclosed_day = &journal.unclosed_days().unwrap()[0];
+    assert!(!unclosed_day.working);
+    assert_eq!(
+        unclosed_day.close_time(CloseAt::LastActivity),
+        Some(time("2021-05-03T10:30:00+02:00"))
+    );
+}
+
+#[test]
+fn the_clock_can_be_stopped_from_the_environment() {
+    std::env::set_var(clock::NOW_VARIABLE, "2021-05-03T23:30:00+00:00");
+    let clock = clock::from_env().unwrap();
+    std::env::remove_var(clock::NOW_VARIABLE);
+
+    let mut journal = journal();
+    journal.set_clock(clock);
+    assert_eq!(journal.now(), time("2021-05-04T01:30:00+02:00"));
+    assert_eq!(
+        journal.today().unwrap(),
+        NaiveDate::from_ymd_opt(2021, 5, 4).unwrap()
+    );
+}
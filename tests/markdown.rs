@@ -0,0 +1,59 @@
+// The log of a day, written as a Markdown checklist.
+
+mod common;
+
+use akiv::markdown::write_day_log;
+use chrono::Duration;
+use common::{at, day, journal, paris};
+
+#[test]
+fn day_logs_list_the_tasks_their_times_and_notes() {
+    let mut journal = journal();
+    at(&mut journal, "09:00");
+    journal
+        .add("Write report", Duration::hours(1), None, None)
+        .unwrap();
+    journal
+        .add("Call", Duration::minutes(15), None, None)
+        .unwrap();
+    journal
+        .add("Review", Duration::minutes(30), None, None)
+        .unwrap();
+    journal.start().unwrap();
+    journal.note(1, "Outline done").unwrap();
+    journal.note(1, "Sent to Bob").unwrap();
+    at(&mut journal, "09:35");
+    journal.next().unwrap();
+    at(&mut journal, "09:45");
+    journal.stop().unwrap();
+    at(&mut journal, "10:00");
+    journal.start().unwrap();
+
+    at(&mut journal, "10:20");
+    assert_eq!(
+        write_day_log(&journal.day(day()).unwrap(), paris()),
+        "## 2021-05-03\n\
+         \n\
+         - [x] Write report (took 35m, estimated 1h)\n    \
+         - Outline done\n    \
+         - Sent to Bob\n\
+         - [ ] Call (in progress, worked 30m, estimated 15m)\n\
+         - [ ] Review (estimated 30m)\n\
+         \n\
+         Pauses:\n\
+         \n\
+         - 09:45 - 10:00 (15m)\n\
+         \n\
+         Worked 1h 5m of 1h 45m estimated.\n"
+    );
+}
+
+#[test]
+fn empty_days_are_logged_as_such() {
+    let mut journal = journal();
+    at(&mut journal, "09:00");
+    assert_eq!(
+        write_day_log(&journal.day(day()).unwrap(), paris()),
+        "## 2021-05-03\n\nNo tasks.\n\nWorked 0m of 0m estimated.\n"
+    );
+}
@@ -0,0 +1,189 @@
+// Merging the journal of another machine.
+
+mod common;
+
+use akiv::merge::{merge, Conflict};
+use akiv::model::GitContext;
+use akiv::storage::{MemoryStorage, NewTask, SqliteStorage, Storage};
+use chrono::{Duration, NaiveTime};
+use rusqlite::Connection;
+use common::{day, temp_dir, time};
+
+const CONTEXT: &str = "default";
+
+fn new_task(uuid: &str, description: &str) -> NewTask {
+    NewTask {
+        uuid: uuid.to_string(),
+        description: description.to_string(),
+        estimated_duration: Duration::minutes(30),
+        fixed_at: None,
+        source_uid: None,
+        created_at: time("2021-05-03T08:00:00Z"),
+    }
+}
+
+/// A journal with the given tasks, by position.
+fn journal(tasks: &[(&str, &str)]) -> MemoryStorage {
+    let mut journal = MemoryStorage::default();
+    for (index, (uuid, description)) in tasks.iter().enumerate() {
+        journal
+            .insert_task(
+                CONTEXT,
+                day(),
+                index as u32 + 1,
+                &new_task(uuid, description),
+            )
+            .unwrap();
+    }
+    journal
+}
+
+fn descriptions(journal: &dyn Storage) -> Vec<String> {
+    journal
+        .tasks(CONTEXT, day())
+        .unwrap()
+        .into_iter()
+        .map(|task| task.description)
+        .collect()
+}
+
+#[test]
+fn tasks_with_the_same_uuid_are_not_added_twice() {
+    let mut ours = journal(&[("a", "Write report"), ("b", "Call")]);
+    let theirs = journal(&[("a", "Write report"), ("b", "Call")]);
+
+    let result = merge(&mut ours, &theirs).unwrap();
+
+    assert_eq!(result.unchanged, 2);
+    assert!(result.added.is_empty());
+    assert!(result.conflicts.is_empty());
+    assert_eq!(descriptions(&ours), vec!["Write report", "Call"]);
+}
+
+#[test]
+fn tasks_are_added_after_the_task_preceding_them() {
+    let mut ours = journal(&[("a", "Write report"), ("c", "Review")]);
+    let theirs = journal(&[("a", "Write report"), ("b", "Call"), ("d", "Mail")]);
+
+    let result = merge(&mut ours, &theirs).unwrap();
+
+    assert_eq!(result.added.len(), 2);
+    assert_eq!(
+        descriptions(&ours),
+        vec!["Write report", "Call", "Mail", "Review"]
+    );
+}
+
+#[test]
+fn different_ends_of_work_conflict() {
+    let mut ours = MemoryStorage::default();
+    let mut theirs = MemoryStorage::default();
+    ours.set_end_of_work(day(), NaiveTime::from_hms_opt(17, 0, 0).unwrap())
+        .unwrap();
+    theirs
+        .set_end_of_work(day(), NaiveTime::from_hms_opt(18, 0, 0).unwrap())
+        .unwrap();
+
+    let result = merge(&mut ours, &theirs).unwrap();
+
+    assert!(matches!(
+        result.conflicts.as_slice(),
+        [Conflict::EndOfWork { .. }]
+    ));
+    assert_eq!(
+        ours.end_of_work(day()).unwrap(),
+        NaiveTime::from_hms_opt(17, 0, 0)
+    );
+}
+
+#[test]
+fn different_git_contexts_conflict() {
+    let mut ours = journal(&[("a", "Write report")]);
+    let mut theirs = journal(&[("a", "Write report")]);
+    let git = GitContext {
+        repository: "/home/me/report".to_string(),
+        branch: Some("main".to_string()),
+        head: "1a2b3c4".to_string(),
+        commits: vec!["5d6e7f8 Add summary".to_string()],
+    };
+    theirs.set_git(CONTEXT, day(), 1, Some(&git)).unwrap();
+
+    let result = merge(&mut ours, &theirs).unwrap();
+
+    assert_eq!(result.unchanged, 0);
+    assert!(matches!(
+        result.conflicts.as_slice(),
+        [Conflict::Task { .. }]
+    ));
+}
+
+#[test]
+fn the_larger_count_of_pomodoros_is_kept() {
+    let mut ours = journal(&[("a", "Write report")]);
+    let mut theirs = journal(&[("a", "Write report")]);
+    ours.set_pomodoros(CONTEXT, day(), 1, 1).unwrap();
+    theirs.set_pomodoros(CONTEXT, day(), 1, 3).unwrap();
+
+    let result = merge(&mut ours, &theirs).unwrap();
+
+    assert!(result.conflicts.is_empty());
+    assert_eq!(ours.tasks(CONTEXT, day()).unwrap()[0].pomodoros, 3);
+}
+
+#[test]
+fn copies_of_a_journal_from_before_uuids_match() {
+    let dir = temp_dir();
+    let laptop = dir.join("laptop.sqlite");
+    let desktop = dir.join("desktop.sqlite");
+
+    // A journal with the first schema, copied before upgrading.
+    {
+        let db = Connection::open(&laptop).unwrap();
+        db.execute_batch(
+            "CREATE TABLE task (
+                 id              INTEGER PRIMARY KEY AUTOINCREMENT,
+                 day             TEXT NOT NULL,
+                 description     TEXT NOT NULL,
+                 position        INTEGER NOT NULL,
+                 created_at      TEXT NOT NULL,
+                 started_at      TEXT,
+                 finished_at     TEXT,
+                 estimated_duration  INTEGER NOT NULL
+             );
+             CREATE UNIQUE INDEX day_position ON task (day, position);
+             CREATE TABLE work (
+                 id              INTEGER PRIMARY KEY AUTOINCREMENT,
+                 day             TEXT NOT NULL,
+                 timestamp       TEXT
+             );
+             CREATE INDEX day_index ON work (day);
+             INSERT INTO task (day, description, position, created_at, estimated_duration)
+                 VALUES ('2021-05-03', 'Write report', 1, '2021-05-03 06:55:02', 1800),
+                        ('2021-05-03', 'Call', 2, '2021-05-03 06:56:10', 900),
+                        ('2021-05-03', 'Call', 3, '2021-05-03 06:56:10', 900);",
+        )
+        .unwrap();
+    }
+    std::fs::copy(&laptop, &desktop).unwrap();
+
+    let mut ours = SqliteStorage::open(&laptop).unwrap();
+    let theirs = SqliteStorage::open(&desktop).unwrap();
+    let uuids = |storage: &SqliteStorage| -> Vec<String> {
+        storage
+            .tasks(CONTEXT, day())
+            .unwrap()
+            .into_iter()
+            .map(|task| task.uuid)
+            .collect()
+    };
+    assert_eq!(uuids(&ours), uuids(&theirs));
+    assert_ne!(uuids(&ours)[1], uuids(&ours)[2]);
+
+    let result = merge(&mut ours, &theirs).unwrap();
+    assert_eq!(result.unchanged, 3);
+    assert!(result.added.is_empty());
+    assert_eq!(descriptions(&ours), vec!["Write report", "Call", "Call"]);
+
+    drop(ours);
+    drop(theirs);
+}
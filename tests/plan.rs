@@ -0,0 +1,93 @@
+// Projections of the plan of a day, from a given time.
+
+mod common;
+
+use akiv::model::Task;
+use akiv::plan::{capacity, project, Projection};
+use chrono::{Duration, NaiveTime};
+use common::{paris, task, time};
+
+/// An appointment at the given time of the day of the scenarios.
+fn appointment(position: u32, description: &str, estimate: Duration, at: &str) -> Task {
+    let mut task = task(position, description, estimate);
+    task.fixed_at = Some(NaiveTime::parse_from_str(at, "%H:%M").unwrap());
+    task
+}
+
+/// The start and end of a projection, as HH:MM in Paris.
+fn times(projection: &Option<Projection>) -> (String, String) {
+    let projection = projection.as_ref().unwrap();
+    let format = |time| paris().localize(time).format("%H:%M").to_string();
+    (format(projection.start), format(projection.end))
+}
+
+#[test]
+fn tasks_flow_around_appointments() {
+    let tasks = vec![
+        task(1, "Write report", Duration::hours(1)),
+        appointment(2, "Meeting", Duration::minutes(30), "10:00"),
+        task(3, "Call", Duration::minutes(30)),
+    ];
+
+    let now = time("2021-05-03T09:30:00+02:00");
+    let projections = project(&tasks, &Vec::new(), &[], now, paris());
+
+    assert_eq!(times(&projections[0]), ("09:30".into(), "11:00".into()));
+    assert_eq!(projections[0].as_ref().unwrap().collisions, vec![2]);
+    assert_eq!(times(&projections[1]), ("10:00".into(), "10:30".into()));
+    assert!(!projections[1].as_ref().unwrap().has_conflict());
+    assert_eq!(times(&projections[2]), ("11:00".into(), "11:30".into()));
+    assert!(projections[2].as_ref().unwrap().collisions.is_empty());
+}
+
+#[test]
+fn appointments_not_started_in_time_are_late() {
+    let tasks = vec![appointment(1, "Meeting", Duration::minutes(30), "09:00")];
+
+    let now = time("2021-05-03T09:10:00+02:00");
+    let projections = project(&tasks, &Vec::new(), &[], now, paris());
+
+    assert!(projections[0].as_ref().unwrap().late);
+    assert_eq!(times(&projections[0]), ("09:00".into(), "09:30".into()));
+}
+
+#[test]
+fn tasks_ending_after_the_end_of_work_overrun() {
+    let tasks = vec![
+        task(1, "Write report", Duration::hours(1)),
+        appointment(2, "Meeting", Duration::minutes(30), "10:00"),
+        task(3, "Call", Duration::minutes(30)),
+    ];
+    let now = time("2021-05-03T09:30:00+02:00");
+    let end_of_work = time("2021-05-03T11:00:00+02:00");
+    let projections = project(&tasks, &Vec::new(), &[], now, paris());
+
+    let capacity = capacity(&tasks, &Vec::new(), &[], &projections, end_of_work, now);
+    assert_eq!(capacity.available, Duration::minutes(90));
+    assert_eq!(capacity.planned, Duration::hours(2));
+    assert_eq!(capacity.overrunning, vec![3]);
+}
+
+#[test]
+fn overlapping_breaks_are_counted_once() {
+    let tasks = vec![task(1, "Write report", Duration::hours(2))];
+    let breaks = vec![
+        (
+            time("2021-05-03T12:00:00+02:00"),
+            time("2021-05-03T13:00:00+02:00"),
+        ),
+        (
+            time("2021-05-03T12:30:00+02:00"),
+            time("2021-05-03T13:30:00+02:00"),
+        ),
+    ];
+    let now = time("2021-05-03T11:00:00+02:00");
+    let end_of_work = time("2021-05-03T18:00:00+02:00");
+
+    let projections = project(&tasks, &Vec::new(), &breaks, now, paris());
+    assert_eq!(times(&projections[0]), ("11:00".into(), "14:30".into()));
+
+    let capacity = capacity(&tasks, &Vec::new(), &breaks, &projections, end_of_work, now);
+    assert_eq!(capacity.available, Duration::minutes(330));
+    assert!(capacity.overrunning.is_empty());
+}
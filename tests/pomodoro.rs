@@ -0,0 +1,167 @@
+// Pomodoros, run on a clock that moves forward instead of sleeping.
+
+mod common;
+
+use akiv::clock::Clock;
+use akiv::config::Config;
+use akiv::pomodoro::{run, Schedule, Step};
+use akiv::storage::Backend;
+use akiv::Journal;
+use chrono::{DateTime, Duration, Local};
+use common::{day, paris, temp_dir, time};
+use std::cell::Cell;
+use std::path::Path;
+use std::rc::Rc;
+
+/// A clock moving forward when slept on.
+struct SteppingClock(Cell<DateTime<Local>>);
+
+impl Clock for SteppingClock {
+    fn now(&self) -> DateTime<Local> {
+        self.0.get()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.0.set(self.0.get() + duration);
+    }
+}
+
+fn schedule() -> Schedule {
+    Schedule {
+        work: Duration::minutes(25),
+        short_break: Duration::minutes(5),
+        long_break: Duration::minutes(15),
+    }
+}
+
+/// Opens the text journal of the directory, on the given clock.
+fn open(dir: &Path, clock: &Rc<SteppingClock>) -> anyhow::Result<Journal> {
+    let config = Config {
+        timezone: paris(),
+        ..Config::default()
+    };
+    let mut journal = Journal::open(&dir.join("journal.txt"), Some(Backend::Text), config)?;
+    journal.set_clock(clock.clone());
+    Ok(journal)
+}
+
+/// The step, and the time it ends at in Paris, if any.
+fn summary(step: &Step) -> String {
+    let until = |until: &DateTime<Local>| paris().localize(*until).format("%H:%M").to_string();
+    match step {
+        Step::Working(task, end) => format!("work on {} until {}", task.description, until(end)),
+        Step::Break(task, end) => format!(
+            "break after {} of {} until {}",
+            task.pomodoros,
+            task.description,
+            until(end)
+        ),
+        other => format!("{:?}", other),
+    }
+}
+
+#[test]
+fn breaks_are_recorded_as_pauses() {
+    let dir = temp_dir();
+    let clock = Rc::new(SteppingClock(Cell::new(time("2021-05-03T09:00:00+02:00"))));
+    let open_journal = || open(&dir, &clock);
+    open_journal()
+        .unwrap()
+        .add("Write report", Duration::hours(4), None, None)
+        .unwrap();
+
+    let mut steps = Vec::new();
+    run(&open_journal, schedule(), &mut |step| {
+        steps.push(summary(step));
+        // Work is stopped by another command during the fifth interval.
+        if steps.len() == 9 {
+            open_journal().unwrap().stop().unwrap();
+        }
+    })
+    .unwrap();
+
+    assert_eq!(
+        steps,
+        vec![
+            "work on Write report until 09:25",
+            "break after 1 of Write report until 09:30",
+            "work on Write report until 09:55",
+            "break after 2 of Write report until 10:00",
+            "work on Write report until 10:25",
+            "break after 3 of Write report until 10:30",
+            "work on Write report until 10:55",
+            "break after 4 of Write report until 11:10",
+            "work on Write report until 11:35",
+            "WorkStopped",
+        ]
+    );
+    let journal = open_journal().unwrap();
+    let pause = |start: &str, end: &str| {
+        (
+            time(&format!("2021-05-03T{}:00+02:00", start)),
+            Some(time(&format!("2021-05-03T{}:00+02:00", end))),
+        )
+    };
+    assert_eq!(
+        journal.pauses(day()).unwrap(),
+        vec![
+            pause("09:25", "09:30"),
+            pause("09:55", "10:00"),
+            pause("10:25", "10:30"),
+            pause("10:55", "11:10"),
+            (time("2021-05-03T11:10:00+02:00"), None),
+        ]
+    );
+    assert_eq!(journal.tasks(day()).unwrap()[0].pomodoros, 4);
+}
+
+#[test]
+fn intervals_only_count_if_the_task_was_worked_on_all_along() {
+    let dir = temp_dir();
+    let clock = Rc::new(SteppingClock(Cell::new(time("2021-05-03T09:00:00+02:00"))));
+    let open_journal = || open(&dir, &clock);
+    let mut journal = open_journal().unwrap();
+    journal
+        .add("Write report", Duration::hours(1), None, None)
+        .unwrap();
+    journal.add("Call", Duration::hours(1), None, None).unwrap();
+
+    let mut steps = Vec::new();
+    run(&open_journal, schedule(), &mut |step| {
+        steps.push(summary(step));
+        let mut journal = open_journal().unwrap();
+        match steps.len() {
+            // 'next' during the first interval.
+            1 => {
+                journal.next().unwrap();
+            }
+            // Work stopped and started again during the second one.
+            3 => {
+                journal.stop().unwrap();
+                journal.start().unwrap();
+            }
+            // Work started during the first break.
+            6 => {
+                journal.start().unwrap();
+            }
+            _ => {}
+        }
+    })
+    .unwrap();
+
+    assert_eq!(
+        steps,
+        vec![
+            "work on Write report until 09:25",
+            "TaskChanged",
+            "work on Call until 09:50",
+            "WorkRestarted",
+            "work on Call until 10:15",
+            "break after 1 of Call until 10:20",
+            "StartedDuringBreak",
+        ]
+    );
+    let tasks = open_journal().unwrap().tasks(day()).unwrap();
+    assert_eq!(tasks[0].pomodoros, 0);
+    assert_eq!(tasks[1].pomodoros, 1);
+}
@@ -0,0 +1,250 @@
+// The HTTP API, with requests handled in memory on a text journal.
+
+mod common;
+
+use akiv::clock::FixedClock;
+use akiv::config::Config;
+use akiv::server::{handle, serve, Method, Request};
+use akiv::storage::Backend;
+use akiv::Journal;
+use common::{day, paris, temp_dir, time, TempDir};
+use serde_json::{json, Value};
+use std::rc::Rc;
+
+const LISTEN: &str = "127.0.0.1:8080";
+
+/// A journal in a directory of its own, opened again for each request
+/// like the server does, at 09:00 in Paris on the day of the scenarios.
+struct Api {
+    dir: TempDir,
+}
+
+impl Api {
+    fn new() -> Api {
+        Api { dir: temp_dir() }
+    }
+
+    fn open_journal(&self) -> anyhow::Result<Journal> {
+        let config = Config {
+            timezone: paris(),
+            ..Config::default()
+        };
+        let mut journal =
+            Journal::open(&self.dir.join("journal.txt"), Some(Backend::Text), config)?;
+        journal.set_clock(Rc::new(FixedClock(time("2021-05-03T09:00:00+02:00"))));
+        Ok(journal)
+    }
+
+    /// Handles a request with the given headers.
+    fn handle(
+        &self,
+        method: Method,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) -> (u16, Value) {
+        let request = Request {
+            method,
+            url: url.to_string(),
+            headers: headers
+                .iter()
+                .map(|(field, value)| (field.to_string(), value.to_string()))
+                .collect(),
+            body: body.as_bytes().to_vec(),
+        };
+        handle(&request, LISTEN, &|| self.open_journal())
+    }
+
+    /// Handles a request sent by a local client, with a JSON body if
+    /// any.
+    fn request(&self, method: Method, url: &str, body: Option<Value>) -> (u16, Value) {
+        match body {
+            Some(body) => self.handle(
+                method,
+                url,
+                &[("Host", LISTEN), ("Content-Type", "application/json")],
+                &body.to_string(),
+            ),
+            None => self.handle(method, url, &[("Host", LISTEN)], ""),
+        }
+    }
+}
+
+fn descriptions(tasks: &Value) -> Vec<&str> {
+    tasks
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|task| task["description"].as_str().unwrap())
+        .collect()
+}
+
+#[test]
+fn the_operations_are_routed() {
+    let api = Api::new();
+    let (status, task) = api.request(
+        Method::Post,
+        "/tasks",
+        Some(json!({"description": "Write report", "estimate": "1h"})),
+    );
+    assert_eq!(status, 201);
+    assert_eq!(task["position"], 1);
+    assert_eq!(task["estimate"], 3600);
+    for description in ["Call", "Review"] {
+        let new_task = json!({"description": description, "estimate": "15m"});
+        assert_eq!(api.request(Method::Post, "/tasks", Some(new_task)).0, 201);
+    }
+
+    let (status, task) = api.request(Method::Post, "/tasks/2/move", Some(json!({"to": 1})));
+    assert_eq!((status, task["position"].clone()), (200, json!(1)));
+    let (status, task) = api.request(Method::Delete, "/tasks/3", None);
+    assert_eq!(
+        (status, task["description"].clone()),
+        (200, json!("Review"))
+    );
+
+    let (status, day) = api.request(Method::Get, "/tasks", None);
+    assert_eq!(status, 200);
+    assert_eq!(day["day"], "2021-05-03");
+    assert_eq!(day["working"], false);
+    assert_eq!(descriptions(&day["tasks"]), vec!["Call", "Write report"]);
+
+    let (status, start) = api.request(Method::Post, "/start", None);
+    assert_eq!(
+        (status, start["started"]["description"].clone()),
+        (200, json!("Call"))
+    );
+    let (status, next) = api.request(Method::Post, "/next", None);
+    assert_eq!(status, 200);
+    assert_eq!(next["finished"]["description"], "Call");
+    assert_eq!(next["started"]["description"], "Write report");
+    let (status, stop) = api.request(Method::Post, "/stop", None);
+    assert_eq!((status, stop), (200, json!({"working": false})));
+
+    let (status, history) =
+        api.request(Method::Get, "/history?from=2021-05-03&to=2021-05-03", None);
+    assert_eq!(status, 200);
+    assert_eq!(descriptions(&history["tasks"]), vec!["Call"]);
+    assert_eq!(history["tasks"][0]["day"], "2021-05-03");
+    let (_, history) = api.request(Method::Get, "/history?to=2021-05-02", None);
+    assert!(descriptions(&history["tasks"]).is_empty());
+}
+
+#[test]
+fn errors_are_answered_with_their_status() {
+    let api = Api::new();
+    let new_task = json!({"description": "Write report", "estimate": "1h"});
+    api.request(Method::Post, "/tasks", Some(new_task));
+    api.request(Method::Post, "/start", None);
+
+    let errors = [
+        // Unexisting tasks.
+        api.request(Method::Delete, "/tasks/9", None),
+        // Operations not allowed in the state of the journal.
+        api.request(Method::Delete, "/tasks/1", None),
+        api.request(Method::Post, "/tasks/1/move", Some(json!({"to": 2}))),
+        api.request(Method::Post, "/start", None),
+        // No estimate given, and none to learn.
+        api.request(Method::Post, "/tasks", Some(json!({"description": "Call"}))),
+        // Invalid requests.
+        api.request(Method::Delete, "/tasks/first", None),
+        api.request(Method::Post, "/tasks", Some(json!({"estimate": "1h"}))),
+        api.request(
+            Method::Post,
+            "/tasks",
+            Some(json!({"description": "Call", "estimate": "soon"})),
+        ),
+        api.request(Method::Get, "/history?from=yesterday", None),
+        // Unknown operations.
+        api.request(Method::Put, "/tasks", None),
+        api.request(Method::Get, "/tasks/1", None),
+        api.request(Method::Get, "/", None),
+    ];
+    let statuses: Vec<u16> = errors.iter().map(|(status, _)| *status).collect();
+    assert_eq!(
+        statuses,
+        vec![404, 409, 409, 409, 422, 400, 400, 400, 400, 405, 405, 404]
+    );
+    for (_, body) in &errors {
+        assert!(body["error"].is_string(), "{}", body);
+    }
+    let journal = api.open_journal().unwrap();
+    assert_eq!(journal.tasks(day()).unwrap().len(), 1);
+}
+
+#[test]
+fn requests_must_be_sent_to_the_api_itself() {
+    let api = Api::new();
+    let get = |headers: &[(&str, &str)]| api.handle(Method::Get, "/tasks", headers, "").0;
+    assert_eq!(get(&[("Host", LISTEN)]), 200);
+    assert_eq!(get(&[("host", "LOCALHOST:8080")]), 200);
+    assert_eq!(get(&[("Host", "[::1]:8080")]), 200);
+    assert_eq!(get(&[]), 403);
+    assert_eq!(get(&[("Host", "attacker.example:8080")]), 403);
+    assert_eq!(get(&[("Host", "localhost:9090")]), 403);
+    // Reading is allowed from other pages, as they cannot see the
+    // answer without the API allowing them.
+    assert_eq!(
+        get(&[("Host", LISTEN), ("Origin", "http://attacker.example")]),
+        200
+    );
+
+    let start = |headers: &[(&str, &str)]| api.handle(Method::Post, "/start", headers, "").0;
+    assert_eq!(
+        start(&[("Host", LISTEN), ("Origin", "http://attacker.example")]),
+        403
+    );
+    assert_eq!(start(&[("Host", LISTEN), ("Origin", "null")]), 403);
+    // No tasks to start, but the request went through.
+    assert_eq!(
+        start(&[("Host", LISTEN), ("Origin", "http://localhost:8080")]),
+        409
+    );
+    assert_eq!(start(&[("Host", LISTEN)]), 409);
+}
+
+#[test]
+fn bodies_must_be_sent_as_json() {
+    let api = Api::new();
+    let body = r#"{"description": "Write report", "estimate": "1h"}"#;
+    let add = |content_type: &str| {
+        api.handle(
+            Method::Post,
+            "/tasks",
+            &[("Host", LISTEN), ("Content-Type", content_type)],
+            body,
+        )
+        .0
+    };
+    assert_eq!(add("text/plain"), 415);
+    assert_eq!(add("application/x-www-form-urlencoded"), 415);
+    assert_eq!(
+        api.handle(Method::Post, "/tasks", &[("Host", LISTEN)], body)
+            .0,
+        415
+    );
+    assert_eq!(add("application/json; charset=utf-8"), 201);
+
+    // Requests without a body need no content type, and unknown
+    // operations are told apart before the body is looked at.
+    assert_eq!(
+        api.handle(Method::Post, "/start", &[("Host", LISTEN)], "")
+            .0,
+        200
+    );
+    let unknown = api.handle(
+        Method::Post,
+        "/tasks/1/done",
+        &[("Host", LISTEN), ("Content-Type", "text/plain")],
+        body,
+    );
+    assert_eq!(unknown.0, 404);
+}
+
+#[test]
+fn the_api_only_listens_on_loopback_addresses() {
+    let api = Api::new();
+    for listen in ["0.0.0.0:8080", "192.0.2.1:8080", "[::]:8080"] {
+        assert!(serve(listen, &|| api.open_journal()).is_err(), "{}", listen);
+    }
+}
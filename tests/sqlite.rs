@@ -0,0 +1,109 @@
+// The SQLite journal, and the migration of older ones.
+
+mod common;
+
+use akiv::storage::{NewTask, SqliteStorage, Storage, DEFAULT_CONTEXT};
+use chrono::Duration;
+use rusqlite::Connection;
+use common::{day, temp_dir, time};
+
+#[test]
+fn timestamps_are_migrated_to_utc() {
+    let dir = temp_dir();
+    let path = dir.join("journal.sqlite");
+
+    // A journal from before the migration, with creation times written
+    // by SQLite without an offset, and the other times in UTC with
+    // fractions of seconds.
+    {
+        let mut storage = SqliteStorage::open(&path).unwrap();
+        let task = NewTask {
+            uuid: "4b1f6e4c-0000-4000-8000-000000000000".to_string(),
+            description: "Write report".to_string(),
+            estimated_duration: Duration::minutes(30),
+            fixed_at: None,
+            source_uid: None,
+            created_at: time("2021-05-03T06:00:00Z"),
+        };
+        storage
+            .insert_task(DEFAULT_CONTEXT, day(), 1, &task)
+            .unwrap();
+        storage
+            .add_work_event(DEFAULT_CONTEXT, day(), time("2021-05-03T07:00:00Z"))
+            .unwrap();
+    }
+    {
+        let db = Connection::open(&path).unwrap();
+        db.execute_batch(
+            "UPDATE task SET
+                 created_at = '2021-05-03 06:55:02',
+                 started_at = '2021-05-03 07:00:00.125+00:00',
+                 finished_at = '2021-05-03 07:41:10.250+00:00';
+             UPDATE work SET timestamp = '2021-05-03 07:00:00.125+00:00';
+             PRAGMA user_version = 10;",
+        )
+        .unwrap();
+    }
+
+    let storage = SqliteStorage::open(&path).unwrap();
+    let task = &storage.tasks(DEFAULT_CONTEXT, day()).unwrap()[0];
+    assert_eq!(task.created_at, time("2021-05-03T06:55:02Z"));
+    assert_eq!(task.started_at, Some(time("2021-05-03T07:00:00Z")));
+    assert_eq!(task.finished_at, Some(time("2021-05-03T07:41:10Z")));
+    assert_eq!(
+        storage.work_events(DEFAULT_CONTEXT, day()).unwrap(),
+        vec![time("2021-05-03T07:00:00Z")]
+    );
+    drop(storage);
+
+    let db = Connection::open(&path).unwrap();
+    let (created_at, started_at): (String, String) = db
+        .query_row("SELECT created_at, started_at FROM task", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap();
+    assert_eq!(created_at, "2021-05-03 06:55:02+00:00");
+    assert_eq!(started_at, "2021-05-03 07:00:00+00:00");
+    let version: u32 = db
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(version, 11);
+}
+
+#[test]
+fn journals_opened_read_only_are_left_as_they_are() {
+    let dir = temp_dir();
+    let path = dir.join("journal.sqlite");
+    {
+        let mut storage = SqliteStorage::open(&path).unwrap();
+        let task = NewTask {
+            uuid: "4b1f6e4c-0000-4000-8000-000000000000".to_string(),
+            description: "Write report".to_string(),
+            estimated_duration: Duration::minutes(30),
+            fixed_at: None,
+            source_uid: None,
+            created_at: time("2021-05-03T06:00:00Z"),
+        };
+        storage
+            .insert_task(DEFAULT_CONTEXT, day(), 1, &task)
+            .unwrap();
+    }
+    let content = std::fs::read(&path).unwrap();
+
+    let mut storage = SqliteStorage::open_read_only(&path).unwrap();
+    assert_eq!(storage.tasks(DEFAULT_CONTEXT, day()).unwrap().len(), 1);
+    assert!(storage
+        .add_work_event(DEFAULT_CONTEXT, day(), time("2021-05-03T07:00:00Z"))
+        .is_err());
+    drop(storage);
+    assert_eq!(std::fs::read(&path).unwrap(), content);
+
+    // Older journals would need to be migrated.
+    Connection::open(&path)
+        .unwrap()
+        .execute_batch("PRAGMA user_version = 10;")
+        .unwrap();
+    let content = std::fs::read(&path).unwrap();
+    assert!(SqliteStorage::open_read_only(&path).is_err());
+    assert_eq!(std::fs::read(&path).unwrap(), content);
+}
@@ -0,0 +1,97 @@
+// Standups: the tasks finished on the last working day, and the ones
+// planned for today.
+
+mod common;
+
+use akiv::clock::FixedClock;
+use akiv::standup::write_standup;
+use akiv::Journal;
+use chrono::{Duration, NaiveDate, NaiveTime};
+use common::{at, journal, time};
+use std::rc::Rc;
+
+/// Adds a task on the given day, at 09:00 in Paris, and finishes it.
+fn finish_on(journal: &mut Journal, day: &str, description: &str) {
+    let start = time(&format!("{}T09:00:00+02:00", day));
+    journal.set_clock(Rc::new(FixedClock(start)));
+    journal
+        .add(description, Duration::minutes(30), None, None)
+        .unwrap();
+    journal.start().unwrap();
+    journal.set_clock(Rc::new(FixedClock(start + Duration::minutes(30))));
+    journal.next().unwrap();
+}
+
+/// A journal with tasks finished on Thursday and Friday, and planned for
+/// Monday 2021-05-03, the day of the scenarios.
+fn journal_after_a_weekend() -> Journal {
+    let mut journal = journal();
+    finish_on(&mut journal, "2021-04-29", "Fix login");
+    finish_on(&mut journal, "2021-04-30", "Write report");
+    at(&mut journal, "08:00");
+    journal
+        .add("Call", Duration::minutes(15), None, None)
+        .unwrap();
+    journal
+        .add(
+            "Meeting",
+            Duration::minutes(30),
+            None,
+            Some(NaiveTime::from_hms_opt(10, 0, 0).unwrap()),
+        )
+        .unwrap();
+    journal
+}
+
+#[test]
+fn the_last_working_day_is_reported() {
+    let journal = journal_after_a_weekend();
+    let standup = journal.standup(None).unwrap();
+    assert_eq!(standup.title, "Friday 2021-04-30");
+    assert_eq!(standup.done, vec!["Write report"]);
+    assert_eq!(standup.planned, vec!["Call", "Meeting at 10:00"]);
+}
+
+#[test]
+fn the_previous_day_is_reported_as_yesterday() {
+    let mut journal = journal();
+    finish_on(&mut journal, "2021-05-02", "Write report");
+    at(&mut journal, "08:00");
+    let standup = journal.standup(None).unwrap();
+    assert_eq!(standup.title, "Yesterday");
+    assert_eq!(standup.done, vec!["Write report"]);
+    assert!(standup.planned.is_empty());
+}
+
+#[test]
+fn tasks_are_reported_since_the_given_day() {
+    let journal = journal_after_a_weekend();
+    let since = NaiveDate::from_ymd_opt(2021, 4, 29).unwrap();
+    let standup = journal.standup(Some(since)).unwrap();
+    assert_eq!(standup.title, "Since Thursday 2021-04-29");
+    assert_eq!(standup.done, vec!["Fix login", "Write report"]);
+}
+
+#[test]
+fn standups_are_written_as_text_or_markdown() {
+    let standup = journal_after_a_weekend().standup(None).unwrap();
+    assert_eq!(
+        write_standup(&standup, false),
+        "Friday 2021-04-30:\n  - Write report\n\nToday:\n  - Call\n  - Meeting at 10:00\n"
+    );
+    assert_eq!(
+        write_standup(&standup, true),
+        "### Friday 2021-04-30\n\n- Write report\n\n### Today\n\n- Call\n- Meeting at 10:00\n"
+    );
+}
+
+#[test]
+fn nothing_is_reported_before_the_first_working_day() {
+    let mut journal = journal();
+    at(&mut journal, "08:00");
+    let standup = journal.standup(None).unwrap();
+    assert_eq!(
+        write_standup(&standup, false),
+        "Before today:\n  Nothing.\n\nToday:\n  Nothing.\n"
+    );
+}
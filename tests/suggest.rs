@@ -0,0 +1,48 @@
+// Estimates suggested from the finished tasks with a similar
+// description.
+
+mod common;
+
+use akiv::model::Task;
+use akiv::suggest::{similar_tasks, suggest};
+use chrono::Duration;
+use common::task;
+
+fn finished(description: &str, minutes: i64) -> (Task, Duration) {
+    (task(1, description, Duration::hours(1)), Duration::minutes(minutes))
+}
+
+#[test]
+fn descriptions_with_more_details_are_similar() {
+    let finished_tasks = vec![
+        finished("Review code for PR", 40),
+        finished("Code reviews", 20),
+        finished("Write the release notes", 60),
+        finished("Review the budget", 90),
+    ];
+
+    let similar = similar_tasks("Code review", &finished_tasks);
+    let descriptions: Vec<&str> = similar
+        .iter()
+        .map(|(task, _)| task.description.as_str())
+        .collect();
+    assert_eq!(descriptions, vec!["Review code for PR", "Code reviews"]);
+}
+
+#[test]
+fn tasks_done_without_time_worked_are_left_out() {
+    let finished_tasks = vec![
+        finished("Code review", 0),
+        finished("Code review", 20),
+        finished("Code review", 30),
+        finished("Code review", 50),
+    ];
+
+    let suggestion = suggest(&similar_tasks("Code review", &finished_tasks)).unwrap();
+    assert_eq!(suggestion.samples, 3);
+    assert_eq!(suggestion.estimate, Duration::minutes(30));
+    assert_eq!(
+        suggestion.spread,
+        (Duration::minutes(20), Duration::minutes(30))
+    );
+}
@@ -0,0 +1,91 @@
+// The journal kept as a text file.
+
+mod common;
+
+use akiv::clock::FixedClock;
+use akiv::config::Config;
+use akiv::storage::{Backend, TextStorage};
+use akiv::Journal;
+use chrono::{Duration, NaiveTime};
+use std::rc::Rc;
+use common::{paris, temp_dir, time};
+
+#[test]
+fn settings_and_events_are_written_on_the_day_of_the_journal() {
+    let dir = temp_dir();
+    let path = dir.join("journal.txt");
+    let config = Config {
+        timezone: paris(),
+        ..Config::default()
+    };
+
+    {
+        let mut journal = Journal::open(&path, Some(Backend::Text), config).unwrap();
+        journal.set_clock(Rc::new(FixedClock(time("2021-05-03T09:00:00+02:00"))));
+        journal.use_context("personal").unwrap();
+        journal
+            .queue_event("http://localhost/hook", "{\"event\":\"start\"}")
+            .unwrap();
+        journal.remove_queued_event(1).unwrap();
+    }
+
+    let content = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = content.lines().skip(1).collect();
+    assert_eq!(
+        lines,
+        vec![
+            "2021-05-03 set context personal",
+            "2021-05-03 queue http://localhost/hook -- {\"event\":\"start\"}",
+            "2021-05-03 dequeue 1",
+        ]
+    );
+
+}
+
+#[test]
+fn tasks_without_a_creation_time_are_rejected() {
+    let dir = temp_dir();
+    let path = dir.join("journal.txt");
+    std::fs::write(
+        &path,
+        "# akiv journal\n2021-05-03 add 1 30m uuid=4b1f6e4c -- Write report\n",
+    )
+    .unwrap();
+
+    let error = TextStorage::open(&path).err().unwrap();
+    assert!(format!("{:#}", error).contains("Missing creation time."));
+
+}
+
+#[test]
+fn days_are_counted_in_the_timezone_of_the_journal() {
+    let dir = temp_dir();
+    let path = dir.join("journal.txt");
+    let config = Config {
+        timezone: paris(),
+        ..Config::default()
+    };
+
+    {
+        let mut journal = Journal::open(&path, Some(Backend::Text), config).unwrap();
+        // Already the next day in Paris.
+        journal.set_clock(Rc::new(FixedClock(time("2021-05-03T23:30:00Z"))));
+        journal.set_setting("webhook-overrun", "some-uuid").unwrap();
+        journal
+            .add_break(
+                NaiveTime::from_hms_opt(12, 30, 0).unwrap(),
+                Duration::minutes(45),
+            )
+            .unwrap();
+    }
+
+    let content = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = content.lines().skip(1).collect();
+    assert_eq!(
+        lines,
+        vec![
+            "2021-05-04 set webhook-overrun some-uuid",
+            "2021-05-04 break 12:30 45m",
+        ]
+    );
+}
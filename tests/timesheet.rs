@@ -0,0 +1,75 @@
+// Timesheets of the finished tasks, grouped and rounded.
+
+mod common;
+
+use akiv::model::Task;
+use akiv::timesheet::{entries, round, write_csv, Grouping, Rounding};
+use chrono::Duration;
+use common::{paris, task, time};
+
+fn finished(position: u32, description: &str, started_at: &str, minutes: i64) -> (Task, Duration) {
+    let mut task = task(position, description, Duration::hours(1));
+    task.started_at = Some(time(started_at));
+    task.finished_at = Some(time(started_at) + Duration::minutes(minutes));
+    (task, Duration::minutes(minutes))
+}
+
+#[test]
+fn durations_are_rounded_to_the_increment() {
+    let cases = [
+        (7, Rounding::Nearest, 6, 6),
+        (9, Rounding::Nearest, 6, 12),
+        (7, Rounding::Up, 6, 12),
+        (22, Rounding::Nearest, 15, 15),
+        (23, Rounding::Nearest, 15, 30),
+        (16, Rounding::Up, 15, 30),
+        (44, Rounding::Nearest, 30, 30),
+        (45, Rounding::Nearest, 30, 60),
+        (30, Rounding::Up, 30, 30),
+        (31, Rounding::Up, 30, 60),
+    ];
+    for (minutes, rounding, increment, expected) in cases {
+        assert_eq!(
+            round(
+                Duration::minutes(minutes),
+                rounding,
+                Duration::minutes(increment)
+            ),
+            Duration::minutes(expected),
+            "{} minutes rounded {:?} to {} minutes",
+            minutes,
+            rounding,
+            increment
+        );
+    }
+}
+
+#[test]
+fn entries_are_grouped_by_tag_before_rounding() {
+    let finished_tasks = vec![
+        finished(1, "Fix login +website", "2021-05-03T09:00:00+02:00", 20),
+        finished(2, "Call Bob", "2021-05-03T09:20:00+02:00", 10),
+        finished(3, "Update footer +website", "2021-05-03T09:30:00+02:00", 20),
+    ];
+
+    let entries = entries(
+        &finished_tasks,
+        Grouping::Tag,
+        Rounding::Up,
+        Duration::minutes(15),
+    );
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].tag, "website");
+    assert_eq!(entries[0].description, "Fix login +website; Update footer +website");
+    assert_eq!(entries[0].duration, Duration::minutes(45));
+    assert_eq!(entries[1].tag, "");
+    assert_eq!(entries[1].duration, Duration::minutes(15));
+
+    let timezone = paris();
+    assert_eq!(
+        write_csv(&entries, timezone),
+        "Start date,Start time,Duration,Project,Description,Hours\r\n\
+         2021-05-03,09:00:00,00:45:00,website,Fix login +website; Update footer +website,0.75\r\n\
+         2021-05-03,09:20:00,00:15:00,,Call Bob,0.25\r\n"
+    );
+}
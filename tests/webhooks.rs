@@ -0,0 +1,182 @@
+// Webhooks: the events queued for the changes of the journal, and how
+// the queue is sent.
+
+mod common;
+
+use akiv::config::{Config, WebhooksConfig};
+use akiv::storage::QueuedEvent;
+use akiv::webhooks::{self, MAX_QUEUED_EVENTS};
+use akiv::Journal;
+use chrono::Duration;
+use common::{at, journal_with, paris, time};
+use serde_json::Value;
+use std::net::TcpListener;
+use std::sync::mpsc;
+use tiny_http::{Response, Server};
+
+/// A journal notifying the given URLs.
+fn journal(urls: &[&str]) -> Journal {
+    journal_with(Config {
+        webhooks: WebhooksConfig {
+            urls: urls.iter().map(|url| url.to_string()).collect(),
+            ..WebhooksConfig::default()
+        },
+        ..Config::default()
+    })
+}
+
+/// Runs a change of the journal, and queues its events.
+fn change(journal: &mut Journal, hour_minute: &str, change: fn(&mut Journal)) {
+    at(journal, hour_minute);
+    let before = webhooks::state(journal).unwrap().unwrap();
+    change(journal);
+    webhooks::queue_changes(journal, &before).unwrap();
+}
+
+/// A queued event, as "event HH:MM task", in Paris.
+fn summary(event: &QueuedEvent) -> String {
+    let body: Value = serde_json::from_str(&event.body).unwrap();
+    format!(
+        "{} {} {}",
+        body["event"].as_str().unwrap(),
+        paris().localize(time(body["at"].as_str().unwrap())).format("%H:%M"),
+        body["task"]["description"].as_str().unwrap_or("-")
+    )
+}
+
+#[test]
+fn changes_are_queued_in_the_order_they_happened() {
+    let mut journal = journal(&["http://localhost:9000/akiv"]);
+    at(&mut journal, "08:00");
+    journal
+        .add("Write report", Duration::minutes(30), None, None)
+        .unwrap();
+    journal
+        .add("Call", Duration::minutes(15), None, None)
+        .unwrap();
+
+    change(&mut journal, "09:00", |journal| {
+        journal.start().unwrap();
+    });
+    change(&mut journal, "09:10", |journal| journal.stop().unwrap());
+    change(&mut journal, "09:20", |journal| {
+        journal.start().unwrap();
+    });
+    change(&mut journal, "10:00", |journal| {
+        journal.next().unwrap();
+    });
+    change(&mut journal, "10:30", |journal| journal.stop().unwrap());
+    change(&mut journal, "10:40", |journal| {
+        journal.start().unwrap();
+    });
+
+    let events: Vec<String> = journal.queued_events().unwrap().iter().map(summary).collect();
+    assert_eq!(
+        events,
+        vec![
+            "resume 09:00 Write report",
+            "start 09:00 Write report",
+            "pause 09:10 Write report",
+            "resume 09:20 Write report",
+            // Reached its estimate at 09:40, the pause aside.
+            "overrun 09:40 Write report",
+            "finish 10:00 Write report",
+            "start 10:00 Call",
+            "overrun 10:15 Call",
+            "pause 10:30 Call",
+            "resume 10:40 Call",
+        ]
+    );
+}
+
+#[test]
+fn only_the_last_events_are_kept() {
+    let url = "http://localhost:9000/akiv";
+    let mut journal = journal(&[url]);
+    at(&mut journal, "08:00");
+    for index in 0..MAX_QUEUED_EVENTS {
+        journal.queue_event(url, &index.to_string()).unwrap();
+    }
+    journal
+        .add("Write report", Duration::minutes(30), None, None)
+        .unwrap();
+
+    change(&mut journal, "09:00", |journal| {
+        journal.start().unwrap();
+    });
+
+    let events = journal.queued_events().unwrap();
+    assert_eq!(events.len(), MAX_QUEUED_EVENTS);
+    assert_eq!(events[0].body, "2");
+    assert_eq!(summary(&events[MAX_QUEUED_EVENTS - 2]), "resume 09:00 Write report");
+    assert_eq!(summary(&events[MAX_QUEUED_EVENTS - 1]), "start 09:00 Write report");
+}
+
+/// A server answering with the status of the last segment of the path,
+/// and telling the paths and bodies it receives.
+fn server() -> (String, mpsc::Receiver<(String, String)>) {
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let address = format!("http://{}", server.server_addr());
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        for mut request in server.incoming_requests() {
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body).unwrap();
+            let status: u16 = request.url().rsplit('/').next().unwrap().parse().unwrap();
+            sender.send((request.url().to_string(), body)).unwrap();
+            request.respond(Response::empty(status)).unwrap();
+        }
+    });
+    (address, receiver)
+}
+
+#[test]
+fn events_are_sent_again_only_after_failures() {
+    let (address, received) = server();
+    let delivered = format!("{}/200", address);
+    let refused = format!("{}/410", address);
+    let failing = format!("{}/503", address);
+    let unreachable = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("http://{}/200", listener.local_addr().unwrap())
+    };
+    let mut journal = journal(&[&delivered, &refused, &failing, &unreachable]);
+    at(&mut journal, "09:00");
+    for event in ["first", "second"] {
+        for url in [&delivered, &refused, &failing, &unreachable] {
+            journal.queue_event(url, event).unwrap();
+        }
+        journal
+            .queue_event("http://localhost:9000/removed", event)
+            .unwrap();
+    }
+
+    webhooks::send(&mut journal).unwrap();
+
+    let kept: Vec<(String, String)> = journal
+        .queued_events()
+        .unwrap()
+        .into_iter()
+        .map(|event| (event.url, event.body))
+        .collect();
+    assert_eq!(
+        kept,
+        vec![
+            (failing.clone(), "first".to_string()),
+            (unreachable.clone(), "first".to_string()),
+            (failing.clone(), "second".to_string()),
+            (unreachable.clone(), "second".to_string()),
+        ]
+    );
+    let received: Vec<(String, String)> = received.try_iter().collect();
+    assert_eq!(
+        received,
+        vec![
+            ("/200".to_string(), "first".to_string()),
+            ("/410".to_string(), "first".to_string()),
+            ("/503".to_string(), "first".to_string()),
+            ("/200".to_string(), "second".to_string()),
+            ("/410".to_string(), "second".to_string()),
+        ]
+    );
+}